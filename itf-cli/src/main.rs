@@ -0,0 +1,321 @@
+//! `itf-lint [--json] <PATH>...`
+//!
+//! Checks that the states of ITF traces decode and that the traces pass
+//! [`LazyTrace::validate`], without running them.
+//!
+//! Each path is either a trace file, a directory, which is searched recursively
+//! for `*.itf.json` files, or a pattern with `*` and `?` wildcards in its last
+//! component, eg. `traces/*.itf.json`, for shells which do not expand them.
+//!
+//! Exits with 0 if all the traces are valid, 1 if some are not, including
+//! trace files which cannot be read, and 2 if the arguments are invalid.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use itf::{ItfValue, LazyTrace};
+use serde_json::json;
+
+const USAGE: &str = "usage: itf-lint [--json] <PATH>...";
+
+/// An issue found in a trace, at `path` if known, eg. `states[3].x`.
+struct Issue {
+    path: Option<String>,
+    message: String,
+}
+
+/// The issues found in a trace file, if any.
+struct Report {
+    file: PathBuf,
+    issues: Vec<Issue>,
+}
+
+fn lint(file: &Path) -> Report {
+    let bytes = match fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Report {
+                file: file.to_path_buf(),
+                issues: vec![Issue {
+                    path: None,
+                    message: format!("cannot read the file: {e}"),
+                }],
+            }
+        }
+    };
+
+    let issues = match itf::from_slice::<LazyTrace<ItfValue>>(&bytes) {
+        Ok(trace) => {
+            let undecoded = trace.iter_states().filter_map(|(_, state)| state.err());
+            let invalid = trace.validate().err().unwrap_or_default();
+
+            undecoded
+                .map(|e| Issue {
+                    path: e.path().map(str::to_string),
+                    message: e.to_string(),
+                })
+                .chain(invalid.into_iter().map(|issue| Issue {
+                    path: Some(format!("states[{}]", issue.state())),
+                    message: issue.to_string(),
+                }))
+                .collect()
+        }
+        Err(e) => vec![Issue {
+            path: e.path().map(str::to_string),
+            message: e.to_string(),
+        }],
+    };
+
+    Report {
+        file: file.to_path_buf(),
+        issues,
+    }
+}
+
+/// Collects the trace files designated by `arg` into `files`.
+fn expand(arg: &str, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let path = Path::new(arg);
+
+    let pattern = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains(['*', '?']) => name.chars().collect::<Vec<_>>(),
+        _ if path.is_dir() => return walk(path, files),
+        _ => {
+            files.push(path.to_path_buf());
+            return Ok(());
+        }
+    };
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut matched = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    matched.retain(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| {
+                matches(&pattern, &name.chars().collect::<Vec<_>>())
+            })
+    });
+    matched.sort();
+
+    if matched.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no file matches"));
+    }
+
+    for path in matched {
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Collects the `*.itf.json` files under `dir` into `files`, in lexicographic order.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if path.to_string_lossy().ends_with(".itf.json") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Matches `name` against `pattern`, where `*` matches any sequence
+/// of characters and `?` any single character.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn print_human(reports: &[Report]) {
+    for report in reports {
+        let file = report.file.display();
+
+        if report.issues.is_empty() {
+            println!("{file}: ok");
+        }
+
+        for issue in &report.issues {
+            println!("{file}: {}", issue.message);
+        }
+    }
+}
+
+fn print_json(reports: &[Report]) {
+    let reports = reports
+        .iter()
+        .map(|report| {
+            let issues = report
+                .issues
+                .iter()
+                .map(|issue| json!({ "path": issue.path, "message": issue.message }))
+                .collect::<Vec<_>>();
+
+            json!({
+                "file": report.file.to_string_lossy(),
+                "valid": issues.is_empty(),
+                "issues": issues,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    println!("{}", serde_json::Value::Array(reports));
+}
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut args = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if arg.starts_with('-') => {
+                eprintln!("itf-lint: unknown option `{arg}`\n{USAGE}");
+                return ExitCode::from(2);
+            }
+            _ => args.push(arg),
+        }
+    }
+
+    if args.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    }
+
+    let mut files = Vec::new();
+
+    for arg in &args {
+        if let Err(e) = expand(arg, &mut files) {
+            eprintln!("itf-lint: {arg}: {e}");
+            return ExitCode::from(2);
+        }
+    }
+
+    let reports = files.iter().map(|file| lint(file)).collect::<Vec<_>>();
+
+    if json {
+        print_json(&reports);
+    } else {
+        print_human(&reports);
+    }
+
+    if reports.iter().all(|report| report.issues.is_empty()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../itf/tests/fixtures")
+    }
+
+    #[test]
+    fn wildcards() {
+        let m = |pattern: &str, name: &str| {
+            matches(
+                &pattern.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            )
+        };
+
+        assert!(m("*.itf.json", "a.itf.json"));
+        assert!(m("t?.itf.json", "t1.itf.json"));
+        assert!(m("*", ""));
+        assert!(!m("*.itf.json", "a.json"));
+        assert!(!m("t?.itf.json", "t.itf.json"));
+    }
+
+    #[test]
+    fn expand_dir_and_pattern() {
+        let mut from_dir = Vec::new();
+        expand(fixtures().to_str().unwrap(), &mut from_dir).unwrap();
+        assert_eq!(from_dir.len(), 2);
+
+        let pattern = fixtures().join("Missionaries*.itf.json");
+        let mut from_pattern = Vec::new();
+        expand(pattern.to_str().unwrap(), &mut from_pattern).unwrap();
+        assert_eq!(
+            from_pattern,
+            [fixtures().join("MissionariesAndCannibals.itf.json")]
+        );
+
+        let pattern = fixtures().join("*.tla");
+        assert!(expand(pattern.to_str().unwrap(), &mut Vec::new()).is_err());
+    }
+
+    /// A path in the temporary directory unique to the calling test and process.
+    fn temp_file(test: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("itf-lint-{test}-{}.itf.json", std::process::id()))
+    }
+
+    #[test]
+    fn lint_fixtures() {
+        let report = lint(&fixtures().join("MissionariesAndCannibals.itf.json"));
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn lint_unreadable() {
+        let report = lint(&temp_file("unreadable"));
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].path, None);
+        assert!(report.issues[0]
+            .message
+            .starts_with("cannot read the file: "));
+    }
+
+    #[test]
+    fn lint_unknown_tag() {
+        let file = temp_file("unknown-tag");
+        let trace = json!({
+            "#meta": {},
+            "vars": ["x"],
+            "states": [{ "x": 1 }, { "x": { "#foo": 1 } }],
+        });
+        fs::write(&file, trace.to_string()).unwrap();
+
+        let report = lint(&file);
+        fs::remove_file(&file).unwrap();
+
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].path.as_deref(), Some("states[1].x"));
+        assert!(report.issues[0].message.contains("unknown tag `#foo`"));
+    }
+}
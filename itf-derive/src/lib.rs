@@ -0,0 +1,168 @@
+//! Derive macros for the [`itf`](https://docs.rs/itf) crate.
+//!
+//! Use them through the `derive` feature of the `itf` crate rather than directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Derives `Deserialize` for a struct whose fields are the variables of an ITF state.
+///
+/// Each field is decoded from the variable of the same name, through the
+/// `Deserialize` impl of its type. The `#meta` section of the state is
+/// skipped unless a field is marked with `#[itf(meta)]`.
+///
+/// ## Attributes
+///
+/// - `#[itf(rename = "mbt::actionTaken")]` decodes the field from the given
+///   variable instead, eg. because it is not a valid Rust identifier.
+/// - `#[itf(meta)]` decodes the `#meta` section of the state into the field,
+///   or defaults it if the state has no `#meta` section. This also works for
+///   the states of a `Trace`, so that a single struct holds both the `#meta`
+///   section and the variables of a state.
+#[proc_macro_derive(ItfState, attributes(itf))]
+pub fn derive_itf_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum FieldKind {
+    Var(String),
+    Meta,
+}
+
+fn field_kind(field: &syn::Field) -> syn::Result<FieldKind> {
+    let mut kind = None;
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("itf") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if kind.is_some() {
+                return Err(meta.error("conflicting `itf` attributes"));
+            }
+
+            if meta.path.is_ident("rename") {
+                let name: LitStr = meta.value()?.parse()?;
+                kind = Some(FieldKind::Var(name.value()));
+                Ok(())
+            } else if meta.path.is_ident("meta") {
+                kind = Some(FieldKind::Meta);
+                Ok(())
+            } else {
+                Err(meta.error("unknown `itf` attribute, expected `rename` or `meta`"))
+            }
+        })?;
+    }
+
+    match kind {
+        Some(kind) => Ok(kind),
+        None => {
+            let ident = field.ident.as_ref().expect("named field");
+            let name = ident.to_string();
+            let name = name.strip_prefix("r#").unwrap_or(&name);
+            Ok(FieldKind::Var(name.to_string()))
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "`ItfState` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`ItfState` can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut inits = Vec::with_capacity(fields.len());
+    let mut names = Vec::with_capacity(fields.len());
+    let mut has_meta = false;
+
+    for field in fields {
+        let ident = &field.ident;
+
+        let init = match field_kind(field)? {
+            FieldKind::Var(name) => {
+                let init = quote! {
+                    #ident: __vars.take::<_, __D::Error>(#name)?
+                };
+                names.push(name);
+                init
+            }
+            FieldKind::Meta if has_meta => {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "only one field can be marked with `#[itf(meta)]`",
+                ))
+            }
+            FieldKind::Meta => {
+                has_meta = true;
+                names.push("#meta".to_string());
+                quote! {
+                    #ident: __vars.take_or_default::<_, __D::Error>("#meta")?
+                }
+            }
+        };
+
+        inits.push(init);
+    }
+
+    let name = &input.ident;
+    let name_str = name.to_string();
+
+    let mut generics = input.generics.clone();
+    {
+        let where_clause = generics.make_where_clause();
+        for param in input.generics.type_params() {
+            let ident = &param.ident;
+            where_clause.predicates.push(syn::parse_quote! {
+                #ident: ::itf::__private::serde::de::DeserializeOwned
+            });
+        }
+    }
+
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut impl_generics = generics.clone();
+    impl_generics.params.insert(0, syn::parse_quote!('de));
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics ::itf::__private::serde::Deserialize<'de> for #name #ty_generics
+        #where_clause
+        {
+            fn deserialize<__D>(__deserializer: __D) -> ::core::result::Result<Self, __D::Error>
+            where
+                __D: ::itf::__private::serde::Deserializer<'de>,
+            {
+                let mut __vars = ::itf::__private::Vars::deserialize_struct(
+                    __deserializer,
+                    #name_str,
+                    &[#(#names),*],
+                )?;
+
+                ::core::result::Result::Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,231 @@
+use num_bigint::BigInt;
+use serde_json::json;
+
+use itf::{
+    trace_from_value, ItfBigInt, ItfError, ItfMap, ItfOption, ItfSet, ItfState, ItfString,
+    StateMeta, Trace,
+};
+
+#[derive(Debug, ItfState)]
+struct Bank {
+    balances: ItfMap<ItfString, ItfBigInt>,
+    owners: ItfSet<ItfString>,
+
+    #[itf(rename = "mbt::actionTaken")]
+    action_taken: String,
+
+    memo: Option<String>,
+}
+
+#[test]
+fn deserialize_state() {
+    let json = json!({
+        "#meta": { "index": 0 },
+        "balances": { "#map": [["alice", { "#bigint": "1234567891011121314151617181920" }]] },
+        "owners": { "#set": ["alice"] },
+        "mbt::actionTaken": "init",
+        "unmodeled": 42,
+    });
+
+    let bank: Bank = serde_json::from_value(json).unwrap();
+
+    assert_eq!(
+        bank.balances.get("alice"),
+        Some(&ItfBigInt::from(
+            "1234567891011121314151617181920".parse::<BigInt>().unwrap()
+        ))
+    );
+    assert!(bank.owners.contains("alice"));
+    assert_eq!(bank.action_taken, "init");
+    assert_eq!(bank.memo, None);
+}
+
+#[test]
+fn deserialize_mbt_state() {
+    #[derive(Debug, serde::Deserialize)]
+    struct DepositPicks {
+        amount: ItfOption<ItfBigInt>,
+        account: ItfOption<String>,
+    }
+
+    #[derive(Debug, ItfState)]
+    struct Deposit {
+        balance: ItfBigInt,
+
+        #[itf(rename = "mbt::actionTaken")]
+        action_taken: String,
+
+        #[itf(rename = "mbt::nondetPicks")]
+        nondet_picks: DepositPicks,
+    }
+
+    let json = json!({
+        "balance": 50,
+        "mbt::actionTaken": "deposit",
+        "mbt::nondetPicks": {
+            "amount": { "tag": "Some", "value": { "#bigint": "50" } },
+            "account": { "tag": "None", "value": { "#tup": [] } },
+        },
+    });
+
+    let state: Deposit = serde_json::from_value(json).unwrap();
+
+    assert_eq!(state.action_taken, "deposit");
+    assert_eq!(*state.nondet_picks.amount, Some(ItfBigInt::from(50)));
+    assert_eq!(*state.nondet_picks.account, None);
+    assert_eq!(state.balance, ItfBigInt::from(50));
+}
+
+#[test]
+fn deserialize_state_missing_field() {
+    let json = json!({
+        "balances": { "#map": [] },
+        "owners": { "#set": [] },
+    });
+
+    let err = serde_json::from_value::<Bank>(json).unwrap_err();
+    assert_eq!(err.to_string(), "missing field `mbt::actionTaken`");
+}
+
+#[test]
+fn deserialize_state_option() {
+    #[derive(Debug, ItfState)]
+    struct Lock {
+        owner: ItfOption<String>,
+    }
+
+    // An absent variable is `None`, while a `null` one is rejected
+    let lock: Lock = serde_json::from_value(json!({})).unwrap();
+    assert_eq!(*lock.owner, None);
+
+    let lock: Lock = serde_json::from_value(json!({ "owner": "alice" })).unwrap();
+    assert_eq!(lock.owner.as_deref(), Some("alice"));
+
+    let err = itf::from_value::<Lock>(&json!({ "owner": null })).unwrap_err();
+    assert_eq!(err.path(), Some("owner"));
+}
+
+#[test]
+fn deserialize_state_with_meta() {
+    #[derive(Debug, ItfState)]
+    struct Counter {
+        #[itf(meta)]
+        meta: StateMeta,
+        count: ItfBigInt,
+    }
+
+    let json = json!({ "#meta": { "index": 3 }, "count": 1 });
+    let counter: Counter = serde_json::from_value(json).unwrap();
+    assert_eq!(counter.meta.index, Some(3));
+    assert_eq!(counter.count, ItfBigInt::from(1));
+
+    let json = json!({ "count": 1 });
+    let counter: Counter = serde_json::from_value(json).unwrap();
+    assert_eq!(counter.meta, StateMeta::default());
+}
+
+#[test]
+fn deserialize_generic_state() {
+    #[derive(Debug, ItfState)]
+    struct Wrapper<T> {
+        value: T,
+    }
+
+    let json = json!({ "value": { "#bigint": "5" } });
+    let wrapper: Wrapper<ItfBigInt> = serde_json::from_value(json).unwrap();
+    assert_eq!(wrapper.value, ItfBigInt::from(5));
+}
+
+#[test]
+fn deserialize_trace() {
+    #[derive(Debug, ItfState)]
+    struct Counter {
+        count: ItfBigInt,
+    }
+
+    let json = json!({
+        "#meta": {},
+        "vars": ["count"],
+        "states": [
+            { "#meta": { "index": 0 }, "count": 0 },
+            { "#meta": { "index": 1 }, "count": { "#bigint": "1" } },
+        ]
+    });
+
+    let trace: Trace<Counter> = trace_from_value(json).unwrap();
+    assert_eq!(trace.states[0].meta.index, Some(0));
+    assert_eq!(trace.states[1].value.count, ItfBigInt::from(1));
+}
+
+#[test]
+fn deserialize_trace_error_path() {
+    let json = json!({
+        "#meta": {},
+        "vars": ["balances", "owners", "mbt::actionTaken"],
+        "states": [
+            {
+                "balances": { "#map": [["alice", 1]] },
+                "owners": { "#set": ["alice"] },
+                "mbt::actionTaken": "init",
+            },
+            {
+                "balances": { "#map": [["alice", 1], ["bob", { "#bigint": "1.5" }]] },
+                "owners": { "#set": ["alice"] },
+                "mbt::actionTaken": "deposit",
+            },
+        ]
+    });
+
+    let err = trace_from_value::<Bank>(json).unwrap_err();
+
+    assert_eq!(err.path(), Some("states[1].balances.#map[1][1]"));
+    assert!(matches!(err.inner(), ItfError::BigIntParse(value) if value == "1.5"));
+}
+
+#[test]
+fn deserialize_trace_with_meta() {
+    #[derive(Debug, ItfState)]
+    struct Counter {
+        #[itf(meta)]
+        meta: StateMeta,
+
+        #[itf(rename = "mbt::actionTaken")]
+        action: String,
+
+        count: ItfBigInt,
+    }
+
+    let json = json!({
+        "#meta": {},
+        "vars": ["count", "mbt::actionTaken"],
+        "states": [
+            { "#meta": { "index": 0 }, "count": 0, "mbt::actionTaken": "init" },
+            { "count": 1, "mbt::actionTaken": "step" },
+        ]
+    });
+
+    let trace: Trace<Counter> = trace_from_value(json).unwrap();
+
+    assert_eq!(trace.states[0].meta.index, Some(0));
+    assert_eq!(trace.states[0].value.meta.index, Some(0));
+    assert_eq!(trace.states[1].value.meta, StateMeta::default());
+    assert_eq!(trace.states[1].value.action, "step");
+    assert_eq!(trace.states[1].value.count, ItfBigInt::from(1));
+
+    assert_eq!(trace.validate_vars(), Ok(()));
+}
+
+#[test]
+fn validate_vars() {
+    let json = json!({
+        "#meta": {},
+        "vars": ["balances", "owners"],
+        "states": []
+    });
+
+    let trace: Trace<Bank> = trace_from_value(json).unwrap();
+    let mismatch = trace.validate_vars().unwrap_err();
+
+    assert_eq!(mismatch.unmodeled, Vec::<String>::new());
+    assert_eq!(mismatch.undeclared, ["mbt::actionTaken", "memo"]);
+}
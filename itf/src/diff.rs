@@ -0,0 +1,344 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// A difference between two values, located by its path from the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// The actual value differs from the expected one
+    Changed {
+        path: String,
+        actual: Value,
+        expected: Value,
+    },
+
+    /// A field, element or entry is present in the actual value only
+    Added { path: String, value: Value },
+
+    /// A field, element or entry is present in the expected value only
+    Removed { path: String, value: Value },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Changed {
+                path,
+                actual,
+                expected,
+            } => write!(f, "~ {path}: {actual} (expected {expected})"),
+            Difference::Added { path, value } => write!(f, "+ {path}: {value}"),
+            Difference::Removed { path, value } => write!(f, "- {path}: {value}"),
+        }
+    }
+}
+
+/// The differences between an actual and an expected state, as computed by [`diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for difference in &self.differences {
+            writeln!(f, "{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the differences between an actual and an expected state.
+///
+/// Both states are serialized to ITF JSON and compared field by field.
+/// The order of the elements of a `#set` and of the entries of a `#map`
+/// is irrelevant.
+///
+/// Returns an error if either state fails to serialize.
+pub fn diff<S>(actual: &S, expected: &S) -> Result<StateDiff, serde_json::Error>
+where
+    S: Serialize,
+{
+    let actual = serde_json::to_value(actual)?;
+    let expected = serde_json::to_value(expected)?;
+
+    let mut differences = Vec::new();
+    diff_values(String::new(), &actual, &expected, &mut differences);
+
+    Ok(StateDiff { differences })
+}
+
+fn tagged<'a>(value: &'a Value, tag: &str) -> Option<&'a Vec<Value>> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(tag)?.as_array(),
+        _ => None,
+    }
+}
+
+/// Sorts the elements of sets and the entries of maps, recursively,
+/// so that equal ITF values have equal canonical forms.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Array(elems) => Value::Array(elems.iter().map(canonical).collect()),
+        Value::Object(obj) => {
+            let mut obj = obj
+                .iter()
+                .map(|(k, v)| (k.clone(), canonical(v)))
+                .collect::<serde_json::Map<_, _>>();
+
+            if obj.len() == 1 {
+                for tag in ["#set", "#map"] {
+                    if let Some(Value::Array(elems)) = obj.get_mut(tag) {
+                        elems.sort_by_cached_key(|e| e.to_string());
+                    }
+                }
+            }
+
+            Value::Object(obj)
+        }
+        _ => value.clone(),
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{path}.{segment}")
+    }
+}
+
+fn diff_values(path: String, actual: &Value, expected: &Value, out: &mut Vec<Difference>) {
+    if let (Some(actual), Some(expected)) = (tagged(actual, "#set"), tagged(expected, "#set")) {
+        return diff_sets(join(&path, "#set"), actual, expected, out);
+    }
+
+    if let (Some(actual), Some(expected)) = (tagged(actual, "#map"), tagged(expected, "#map")) {
+        return diff_maps(join(&path, "#map"), actual, expected, out);
+    }
+
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, a) in actual {
+                match expected.get(key) {
+                    Some(e) => diff_values(join(&path, key), a, e, out),
+                    None => out.push(Difference::Added {
+                        path: join(&path, key),
+                        value: a.clone(),
+                    }),
+                }
+            }
+
+            for (key, e) in expected {
+                if !actual.contains_key(key) {
+                    out.push(Difference::Removed {
+                        path: join(&path, key),
+                        value: e.clone(),
+                    });
+                }
+            }
+        }
+
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (i, a) in actual.iter().enumerate() {
+                let elem_path = format!("{path}[{i}]");
+                match expected.get(i) {
+                    Some(e) => diff_values(elem_path, a, e, out),
+                    None => out.push(Difference::Added {
+                        path: elem_path,
+                        value: a.clone(),
+                    }),
+                }
+            }
+
+            for (i, e) in expected.iter().enumerate().skip(actual.len()) {
+                out.push(Difference::Removed {
+                    path: format!("{path}[{i}]"),
+                    value: e.clone(),
+                });
+            }
+        }
+
+        _ => {
+            if canonical(actual) != canonical(expected) {
+                out.push(Difference::Changed {
+                    path,
+                    actual: actual.clone(),
+                    expected: expected.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn diff_sets(path: String, actual: &[Value], expected: &[Value], out: &mut Vec<Difference>) {
+    let actual_canon = actual.iter().map(canonical).collect::<Vec<_>>();
+    let expected_canon = expected.iter().map(canonical).collect::<Vec<_>>();
+
+    for (a, canon) in actual.iter().zip(&actual_canon) {
+        if !expected_canon.contains(canon) {
+            out.push(Difference::Added {
+                path: path.clone(),
+                value: a.clone(),
+            });
+        }
+    }
+
+    for (e, canon) in expected.iter().zip(&expected_canon) {
+        if !actual_canon.contains(canon) {
+            out.push(Difference::Removed {
+                path: path.clone(),
+                value: e.clone(),
+            });
+        }
+    }
+}
+
+fn diff_maps(path: String, actual: &[Value], expected: &[Value], out: &mut Vec<Difference>) {
+    fn entries(map: &[Value]) -> Vec<(Value, &Value, &Value)> {
+        map.iter()
+            .filter_map(|entry| match entry.as_array()?.as_slice() {
+                [k, v] => Some((canonical(k), k, v)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    let actual = entries(actual);
+    let expected = entries(expected);
+
+    for (canon, k, a) in &actual {
+        let entry_path = format!("{path}[{k}]");
+        match expected.iter().find(|(c, _, _)| c == canon) {
+            Some((_, _, e)) => diff_values(entry_path, a, e, out),
+            None => out.push(Difference::Added {
+                path: entry_path,
+                value: (*a).clone(),
+            }),
+        }
+    }
+
+    for (canon, k, e) in &expected {
+        if !actual.iter().any(|(c, _, _)| c == canon) {
+            out.push(Difference::Removed {
+                path: format!("{path}[{k}]"),
+                value: (*e).clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn equal_states() {
+        let state = json!({ "x": 1, "s": { "#set": [1, 2] } });
+        assert!(diff(&state, &state).unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_and_map_order_is_irrelevant() {
+        let actual = json!({
+            "s": { "#set": [{ "#set": [1, 2] }, { "#set": [3] }] },
+            "m": { "#map": [[{ "#tup": [1, "a"] }, 1], [{ "#tup": [2, "b"] }, 2]] },
+        });
+
+        let expected = json!({
+            "s": { "#set": [{ "#set": [3] }, { "#set": [2, 1] }] },
+            "m": { "#map": [[{ "#tup": [2, "b"] }, 2], [{ "#tup": [1, "a"] }, 1]] },
+        });
+
+        assert_eq!(diff(&actual, &expected).unwrap(), StateDiff::default());
+    }
+
+    #[test]
+    fn tuple_order_is_relevant() {
+        let actual = json!({ "t": { "#tup": [1, 2] } });
+        let expected = json!({ "t": { "#tup": [2, 1] } });
+
+        assert_eq!(
+            diff(&actual, &expected).unwrap().differences,
+            vec![
+                Difference::Changed {
+                    path: "t.#tup[0]".to_string(),
+                    actual: json!(1),
+                    expected: json!(2),
+                },
+                Difference::Changed {
+                    path: "t.#tup[1]".to_string(),
+                    actual: json!(2),
+                    expected: json!(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn field_by_field() {
+        let actual = json!({
+            "balances": { "#map": [["alice", 10], ["bob", 5]] },
+            "owners": { "#set": ["alice", "carol"] },
+            "step": 3,
+        });
+
+        let expected = json!({
+            "balances": { "#map": [["alice", 10], ["bob", 7], ["eve", 1]] },
+            "owners": { "#set": ["alice", "bob"] },
+            "outcome": "SUCCESS",
+            "step": 3,
+        });
+
+        let diff = diff(&actual, &expected).unwrap();
+
+        assert_eq!(
+            diff.to_string(),
+            r#"~ balances.#map["bob"]: 5 (expected 7)
+- balances.#map["eve"]: 1
++ owners.#set: "carol"
+- owners.#set: "bob"
+- outcome: "SUCCESS"
+"#
+        );
+    }
+
+    #[test]
+    fn typed_states() {
+        use crate::{ItfBigInt, ItfSet};
+
+        #[derive(Serialize)]
+        struct State {
+            counter: ItfBigInt,
+            seen: ItfSet<i64>,
+        }
+
+        let actual = State {
+            counter: ItfBigInt::from(1),
+            seen: (0..100).collect(),
+        };
+
+        let expected = State {
+            counter: ItfBigInt::from(2),
+            seen: (0..100).rev().collect(),
+        };
+
+        assert_eq!(
+            diff(&actual, &expected).unwrap().differences,
+            vec![Difference::Changed {
+                path: "counter".to_string(),
+                actual: json!(1),
+                expected: json!(2),
+            }]
+        );
+    }
+}
@@ -0,0 +1,647 @@
+use std::{cell::RefCell, fmt, fmt::Display};
+
+use serde::de::{Expected, Unexpected};
+use serde_json::Value;
+
+/// The maximum length of the snippet of JSON displayed by [`ItfError::Snippet`].
+const MAX_SNIPPET_LEN: usize = 512;
+
+/// An error which occurred while decoding an ITF value or trace.
+///
+/// The kind and path of the errors raised while decoding are only known
+/// when decoding with the entrypoints of this crate, eg. [`Trace::from_slice`]
+/// or [`from_value`], whose error type is `ItfError`. Decoding with
+/// `serde_json` directly yields a `serde_json::Error`, which only keeps
+/// the message of the error.
+///
+/// [`Trace::from_slice`]: crate::Trace::from_slice
+/// [`from_value`]: crate::from_value
+#[derive(Debug)]
+pub enum ItfError {
+    TupleArity {
+        expected: usize,
+        found: usize,
+    },
+
+    UnknownTag(String),
+
+    BigIntParse(String),
+
+    IntOverflow(String),
+
+    DuplicateElement,
+
+    MissingState {
+        index: usize,
+        len: usize,
+    },
+
+    Custom(String),
+
+    /// A JSON syntax or I/O error, or an error of `serde_json`
+    /// converted with `From`.
+    Json(serde_json::Error),
+
+    /// An error which occurred while decoding the value at `path`,
+    /// eg. `states[3].balances.#map[2][1]`.
+    At {
+        path: String,
+        segments: Vec<PathSegment>,
+        error: Box<ItfError>,
+    },
+
+    /// A located error, along with the JSON value at its path.
+    /// The value is pretty-printed but truncated when displayed.
+    Snippet {
+        error: Box<ItfError>,
+        snippet: Box<Value>,
+    },
+}
+
+impl fmt::Display for ItfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        offer(self);
+
+        match self {
+            Self::TupleArity { expected, found } => {
+                write!(
+                    f,
+                    "expected tuple with {expected} elements but found {found}"
+                )
+            }
+            Self::UnknownTag(tag) => write!(f, "unknown tag `{tag}`"),
+            Self::BigIntParse(value) => write!(f, "invalid big integer `{value}`"),
+            Self::IntOverflow(value) => write!(f, "integer `{value}` does not fit in an `i128`"),
+            Self::DuplicateElement => f.write_str("duplicate element in `#set`"),
+            Self::MissingState { index, len } => {
+                write!(f, "no state at index {index}, the trace has {len} states")
+            }
+            Self::Custom(msg) => f.write_str(msg),
+            Self::Json(e) => Display::fmt(e, f),
+            Self::At { path, error, .. } => write!(f, "at `{path}`: {error}"),
+            Self::Snippet { error, snippet } => {
+                write!(f, "{error}, in:\n{}", render_snippet(snippet))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ItfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+/// A step of the path to a value which failed to decode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A key of a JSON object, ie. a variable, the field of a record,
+    /// or an ITF tag such as `#map`
+    Key(String),
+
+    /// An index in a JSON array
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) => f.write_str(key),
+            Self::Index(index) => write!(f, "[{index}]"),
+        }
+    }
+}
+
+impl ItfError {
+    /// Returns the path to the value which failed to decode, if known.
+    ///
+    /// The path is only meant to be displayed, as the keys it goes through
+    /// may contain `.` or `[`; use [`ItfError::path_segments`] to walk it.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::At { path, .. } => Some(path),
+            Self::Snippet { error, .. } => error.path(),
+            _ => None,
+        }
+    }
+
+    /// Returns the segments of the path to the value which failed to decode, if known.
+    pub fn path_segments(&self) -> Option<&[PathSegment]> {
+        match self {
+            Self::At { segments, .. } => Some(segments),
+            Self::Snippet { error, .. } => error.path_segments(),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its location.
+    pub fn inner(&self) -> &ItfError {
+        match self {
+            Self::At { error, .. } => error,
+            Self::Snippet { error, .. } => error.inner(),
+            _ => self,
+        }
+    }
+
+    /// Returns the JSON value which failed to decode, if attached
+    /// with [`ItfError::with_snippet`].
+    pub fn snippet(&self) -> Option<&Value> {
+        match self {
+            Self::Snippet { snippet, .. } => Some(snippet),
+            _ => None,
+        }
+    }
+
+    /// Attaches the value found at the path of this error in `json`, the
+    /// document which failed to decode, so that it is displayed along with
+    /// the error. Errors without a path, or whose path cannot be found in
+    /// `json`, are returned unchanged.
+    pub fn with_snippet(self, json: &Value) -> Self {
+        let snippet = match self.path_segments().and_then(|path| resolve(json, path)) {
+            Some(snippet) if !matches!(self, Self::Snippet { .. }) => snippet.clone(),
+            _ => return self,
+        };
+
+        Self::Snippet {
+            error: Box::new(self),
+            snippet: Box::new(snippet),
+        }
+    }
+
+    /// Duplicates the error, keeping only the message of a [`ItfError::Json`] error.
+    fn duplicate(&self) -> Self {
+        match self {
+            Self::TupleArity { expected, found } => Self::TupleArity {
+                expected: *expected,
+                found: *found,
+            },
+            Self::UnknownTag(tag) => Self::UnknownTag(tag.clone()),
+            Self::BigIntParse(value) => Self::BigIntParse(value.clone()),
+            Self::IntOverflow(value) => Self::IntOverflow(value.clone()),
+            Self::DuplicateElement => Self::DuplicateElement,
+            Self::MissingState { index, len } => Self::MissingState {
+                index: *index,
+                len: *len,
+            },
+            Self::Custom(msg) => Self::Custom(msg.clone()),
+            Self::Json(e) => Self::Custom(e.to_string()),
+            Self::At {
+                path,
+                segments,
+                error,
+            } => Self::At {
+                path: path.clone(),
+                segments: segments.clone(),
+                error: Box::new(error.duplicate()),
+            },
+            Self::Snippet { error, snippet } => Self::Snippet {
+                error: Box::new(error.duplicate()),
+                snippet: snippet.clone(),
+            },
+        }
+    }
+}
+
+/// An error given as the message is kept as is, so that the errors raised
+/// by this crate keep their kind and path when decoding into an `ItfError`.
+/// Any other message is a [`ItfError::Custom`] error.
+impl serde::de::Error for ItfError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        match capture(&msg) {
+            (_, Some(err)) => err,
+            (msg, None) => Self::Custom(msg),
+        }
+    }
+
+    // Like `serde_json`, report a unit as a `null`, so that messages do not
+    // depend on the entrypoint the value was decoded with
+
+    fn invalid_type(unexp: Unexpected<'_>, exp: &dyn Expected) -> Self {
+        Self::custom(format_args!(
+            "invalid type: {}, expected {exp}",
+            JsonUnexpected(unexp)
+        ))
+    }
+
+    fn invalid_value(unexp: Unexpected<'_>, exp: &dyn Expected) -> Self {
+        Self::custom(format_args!(
+            "invalid value: {}, expected {exp}",
+            JsonUnexpected(unexp)
+        ))
+    }
+}
+
+struct JsonUnexpected<'a>(Unexpected<'a>);
+
+impl Display for JsonUnexpected<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Unexpected::Unit => f.write_str("null"),
+            unexp => unexp.fmt(f),
+        }
+    }
+}
+
+/// Keeps the error as is, as a [`ItfError::Json`] error.
+impl From<serde_json::Error> for ItfError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+thread_local! {
+    /// The slot which [`capture`] opens while it displays a message, filled by
+    /// the first `ItfError` which is displayed, if any.
+    static CAPTURED: RefCell<Option<Option<ItfError>>> = const { RefCell::new(None) };
+}
+
+/// Displays `msg`, and returns the `ItfError` it is, if any.
+///
+/// Serde only lets generic code build an error from a message, ie. a value
+/// which implements `Display`, so an `ItfError` given as the message is
+/// recognized as it displays itself, within this call. Nothing outlives
+/// the call, so that errors never depend on previous or concurrent ones.
+fn capture(msg: &dyn Display) -> (String, Option<ItfError>) {
+    let outer = CAPTURED.with(|slot| slot.replace(Some(None)));
+    let rendered = msg.to_string();
+    let captured = CAPTURED.with(|slot| slot.replace(None)).flatten();
+
+    // The message may only quote an `ItfError`, eg. if it is a user error wrapping one
+    let captured = captured.filter(|err| err.to_string() == rendered);
+
+    CAPTURED.with(|slot| slot.replace(outer));
+    (rendered, captured)
+}
+
+/// Fills the slot opened by [`capture`] with `err`, unless it is filled already.
+fn offer(err: &ItfError) {
+    CAPTURED.with(|slot| {
+        if let Ok(mut slot) = slot.try_borrow_mut() {
+            if let Some(captured @ None) = &mut *slot {
+                *captured = Some(err.duplicate());
+            }
+        }
+    });
+}
+
+/// Reports an error which occurred while decoding an ITF value, as is if
+/// `E` is an `ItfError` and by its message otherwise.
+pub(crate) fn raise<E>(err: ItfError) -> E
+where
+    E: serde::de::Error,
+{
+    E::custom(err)
+}
+
+/// Finds the value at `path` in `json`.
+fn resolve<'a>(json: &'a Value, path: &[PathSegment]) -> Option<&'a Value> {
+    path.iter().try_fold(json, |value, segment| match segment {
+        PathSegment::Key(key) => value.as_object()?.get(key),
+        PathSegment::Index(index) => value.as_array()?.get(*index),
+    })
+}
+
+/// Renders a path as eg. `states[3].balances.#map[2][1]`.
+fn render_path(path: &[PathSegment]) -> String {
+    let mut rendered = String::new();
+
+    for segment in path {
+        if matches!(segment, PathSegment::Key(_)) && !rendered.is_empty() {
+            rendered.push('.');
+        }
+        rendered.push_str(&segment.to_string());
+    }
+
+    rendered
+}
+
+fn render_snippet(snippet: &Value) -> String {
+    let rendered = serde_json::to_string_pretty(snippet).unwrap_or_default();
+
+    match rendered.char_indices().nth(MAX_SNIPPET_LEN) {
+        Some((end, _)) => format!("{}...", &rendered[..end]),
+        None => rendered,
+    }
+}
+
+/// Strips the ` at line X column Y` suffix which `serde_json` appends to its errors.
+fn strip_position(msg: &str) -> &str {
+    let is_position = |position: &str| {
+        position
+            .split_once(" column ")
+            .map_or(false, |(line, column)| {
+                line.parse::<usize>().is_ok() && column.parse::<usize>().is_ok()
+            })
+    };
+
+    match msg.rsplit_once(" at line ") {
+        Some((msg, position)) if is_position(position) => msg,
+        _ => msg,
+    }
+}
+
+/// The location of a value relative to the value it is part of, as given to
+/// [`located`], eg. `"x"` for a field, `("#set", 1)` for an element of a set,
+/// or `("#map", 2, 1)` for the value of an entry of a map.
+pub(crate) trait Location {
+    fn segments(self) -> Vec<PathSegment>;
+}
+
+impl Location for &str {
+    fn segments(self) -> Vec<PathSegment> {
+        vec![PathSegment::Key(self.to_string())]
+    }
+}
+
+impl Location for usize {
+    fn segments(self) -> Vec<PathSegment> {
+        vec![PathSegment::Index(self)]
+    }
+}
+
+/// An element of an array, tagged unless `tag` is empty.
+impl Location for (&str, usize) {
+    fn segments(self) -> Vec<PathSegment> {
+        let (tag, index) = self;
+
+        match tag {
+            "" => vec![PathSegment::Index(index)],
+            tag => vec![PathSegment::Key(tag.to_string()), PathSegment::Index(index)],
+        }
+    }
+}
+
+impl Location for (&str, usize, usize) {
+    fn segments(self) -> Vec<PathSegment> {
+        let (tag, index, inner) = self;
+
+        let mut segments = (tag, index).segments();
+        segments.push(PathSegment::Index(inner));
+        segments
+    }
+}
+
+/// Locates an error which occurred while decoding the value at `location`,
+/// relative to the value being decoded. An `ItfError` which is already
+/// located gets `location` prepended to its path; any other error is
+/// located as a [`ItfError::Custom`] error.
+pub(crate) fn located<E>(location: impl Location, err: impl Display) -> E
+where
+    E: serde::de::Error,
+{
+    let mut segments = location.segments();
+
+    let error = match capture(&err) {
+        (_, Some(ItfError::Snippet { error, .. })) => *error,
+        (_, Some(err)) => err,
+        (msg, None) => ItfError::Custom(strip_position(&msg).to_string()),
+    };
+
+    let error = match error {
+        ItfError::At {
+            segments: inner,
+            error,
+            ..
+        } => {
+            segments.extend(inner);
+            *error
+        }
+        error => error,
+    };
+
+    E::custom(ItfError::At {
+        path: render_path(&segments),
+        segments,
+        error: Box::new(error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::de::Error as _;
+
+    use crate::{ItfBigInt, ItfInt, ItfList, ItfMap, ItfSet, ItfString, ItfTuple};
+
+    #[test]
+    fn tuple_arity() {
+        let err = crate::from_str::<ItfTuple<(ItfInt, ItfInt)>>(r##"{"#tup": [1]}"##).unwrap_err();
+
+        assert!(matches!(
+            err,
+            ItfError::TupleArity {
+                expected: 2,
+                found: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn nested_tuple_arity() {
+        let json = serde_json::json!({
+            "#tup": [1, { "#tup": ["a", "b", "c"] }]
+        });
+
+        let err = crate::from_value::<ItfTuple<(ItfInt, ItfTuple<(ItfString, ItfString)>)>>(&json)
+            .unwrap_err();
+
+        assert_eq!(err.path(), Some("#tup[1]"));
+        assert!(matches!(
+            err.inner(),
+            ItfError::TupleArity {
+                expected: 2,
+                found: 3
+            }
+        ));
+    }
+
+    #[test]
+    fn nested_bigint_path() {
+        let json = r##"{
+            "#map": [
+                ["alice", { "#set": [] }],
+                ["bob", { "#set": [{ "#tup": [1, [{ "#bigint": "2" }, { "#bigint": "x" }]] }] }]
+            ]
+        }"##;
+
+        let err = crate::from_str::<
+            ItfMap<ItfString, ItfSet<ItfTuple<(ItfInt, ItfList<ItfBigInt>)>>>,
+        >(json)
+        .unwrap_err();
+
+        assert_eq!(err.path(), Some("#map[1][1].#set[0].#tup[1][1]"));
+        assert!(matches!(err.inner(), ItfError::BigIntParse(value) if value == "x"));
+        assert_eq!(
+            err.to_string(),
+            "at `#map[1][1].#set[0].#tup[1][1]`: invalid big integer `x`"
+        );
+    }
+
+    #[test]
+    fn key_like_path() {
+        let json = serde_json::json!({
+            "a`: b.c[0]": { "#tup": [1, { "#bigint": "x" }] }
+        });
+
+        let err =
+            crate::from_value::<crate::ItfObjectMap<ItfTuple<(ItfInt, ItfBigInt)>>>(&json.clone())
+                .unwrap_err();
+
+        let err = err.with_snippet(&json);
+        assert_eq!(
+            err.path_segments(),
+            Some(
+                &[
+                    PathSegment::Key("a`: b.c[0]".to_string()),
+                    PathSegment::Key("#tup".to_string()),
+                    PathSegment::Index(1),
+                ][..]
+            )
+        );
+        assert_eq!(err.path(), Some("a`: b.c[0].#tup[1]"));
+        assert!(matches!(err.inner(), ItfError::BigIntParse(value) if value == "x"));
+        assert_eq!(err.snippet(), Some(&serde_json::json!({ "#bigint": "x" })));
+    }
+
+    #[test]
+    fn snippet() {
+        let json = serde_json::json!({
+            "#map": [["alice", { "#tup": [1, { "#bigint": "x" }] }]]
+        });
+
+        let err =
+            crate::from_value::<ItfMap<ItfString, ItfTuple<(ItfInt, ItfBigInt)>>>(&json.clone())
+                .unwrap_err();
+
+        let err = err.with_snippet(&json);
+        assert_eq!(err.path(), Some("#map[0][1].#tup[1]"));
+        assert_eq!(err.snippet(), Some(&serde_json::json!({ "#bigint": "x" })));
+        assert!(matches!(err.inner(), ItfError::BigIntParse(_)));
+        assert_eq!(
+            err.to_string(),
+            "at `#map[0][1].#tup[1]`: invalid big integer `x`, in:\n{\n  \"#bigint\": \"x\"\n}"
+        );
+
+        // Long snippets are truncated
+        let json = serde_json::json!({ "#tup": [1, "a".repeat(1000)] });
+        let err = crate::from_value::<ItfTuple<(ItfInt, ItfInt)>>(&json).unwrap_err();
+        let err = err.with_snippet(&json);
+        let msg = err.to_string();
+        let (_, snippet) = msg.split_once(", in:\n").unwrap();
+        assert_eq!(snippet.len(), MAX_SNIPPET_LEN + "...".len());
+        assert!(snippet.ends_with("aaa..."));
+
+        // Errors without a path are unchanged
+        let err = ItfError::DuplicateElement.with_snippet(&json);
+        assert!(err.snippet().is_none());
+    }
+
+    #[test]
+    fn bigint_parse() {
+        let err = crate::from_str::<ItfBigInt>(r##"{"#bigint": "12x"}"##).unwrap_err();
+
+        match err {
+            ItfError::BigIntParse(value) => assert_eq!(value, "12x"),
+            err => panic!("unexpected error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn custom() {
+        let raised: ItfError = raise(ItfError::UnknownTag("#foo".to_string()));
+        assert!(matches!(raised, ItfError::UnknownTag(tag) if tag == "#foo"));
+
+        assert!(matches!(
+            ItfError::custom(ItfError::DuplicateElement),
+            ItfError::DuplicateElement
+        ));
+
+        // Messages are kept as is, even if they look like ours
+        assert!(matches!(
+            ItfError::custom("unknown tag `#foo`"),
+            ItfError::Custom(msg) if msg == "unknown tag `#foo`"
+        ));
+
+        // As are messages which merely quote an error
+        assert!(matches!(
+            ItfError::custom(format_args!("in x: {}", ItfError::DuplicateElement)),
+            ItfError::Custom(msg) if msg == "in x: duplicate element in `#set`"
+        ));
+
+        // Errors swallowed before do not leak into later ones
+        let _ = crate::from_value::<ItfBigInt>(&serde_json::json!({ "#bigint": "x" }));
+        assert!(matches!(
+            ItfError::custom("invalid big integer `x`"),
+            ItfError::Custom(_)
+        ));
+    }
+
+    #[test]
+    fn error_sent_across_threads() {
+        let err = std::thread::spawn(|| {
+            crate::from_str::<ItfSet<ItfBigInt>>(r##"{"#set": [{"#bigint": "x"}]}"##).unwrap_err()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(err.path(), Some("#set[0]"));
+        assert!(matches!(err.inner(), ItfError::BigIntParse(value) if value == "x"));
+    }
+
+    #[test]
+    fn serde_json_error_is_json() {
+        let err = serde_json::from_str::<ItfBigInt>(r##"{"#bigint": "x"}"##).unwrap_err();
+        let err = ItfError::from(err);
+
+        assert!(matches!(err, ItfError::Json(_)));
+        assert_eq!(
+            err.to_string(),
+            "invalid big integer `x` at line 1 column 16"
+        );
+    }
+
+    #[test]
+    fn user_error_in_nested_value() {
+        #[derive(Debug)]
+        struct Tag;
+
+        impl<'de> serde::Deserialize<'de> for Tag {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let _ = String::deserialize(deserializer)?;
+                Err(D::Error::custom("unknown tag `x`"))
+            }
+        }
+
+        let err =
+            crate::from_str::<ItfTuple<(ItfInt, Tag)>>(r##"{"#tup": [1, "a"]}"##).unwrap_err();
+
+        assert_eq!(err.path(), Some("#tup[1]"));
+        assert!(matches!(err.inner(), ItfError::Custom(msg) if msg == "unknown tag `x`"));
+    }
+
+    #[test]
+    fn syntax() {
+        let err = crate::from_str::<ItfBigInt>(r##"{"#bigint": "#"##).unwrap_err();
+        assert!(matches!(err, ItfError::Json(_)));
+    }
+
+    #[test]
+    fn data_error_keeps_message() {
+        let err = crate::from_str::<ItfInt>(r#""hello""#).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            r#"invalid type: string "hello", expected i64"#
+        );
+    }
+}
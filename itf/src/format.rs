@@ -0,0 +1,53 @@
+use crate::TraceMeta;
+
+/// The model checker which produced a trace.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    Apalache,
+    Quint,
+}
+
+impl Format {
+    /// Detects the producer of a trace from its `#meta` section,
+    /// based on its `description` and `source` fields.
+    pub fn detect(meta: &TraceMeta) -> Option<Self> {
+        let description = meta.description.as_deref().unwrap_or_default();
+        let source = meta.source.as_deref().unwrap_or_default();
+
+        if description.starts_with("Created by Quint") || source.ends_with(".qnt") {
+            Some(Self::Quint)
+        } else if description.starts_with("Created by Apalache") || source.ends_with(".tla") {
+            Some(Self::Apalache)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        let meta = |source: &str, description: &str| TraceMeta {
+            source: Some(source.to_string()),
+            description: Some(description.to_string()),
+            ..TraceMeta::default()
+        };
+
+        assert_eq!(
+            Format::detect(&meta("counter.qnt", "")),
+            Some(Format::Quint)
+        );
+        assert_eq!(
+            Format::detect(&meta("", "Created by Apalache on Sat Sep 24 2022")),
+            Some(Format::Apalache)
+        );
+        assert_eq!(
+            Format::detect(&meta("MC_counter.tla", "")),
+            Some(Format::Apalache)
+        );
+        assert_eq!(Format::detect(&TraceMeta::default()), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{borrow::Borrow, cell::RefCell, collections::HashSet, fmt, ops::Deref, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+thread_local! {
+    static POOL: RefCell<HashSet<Arc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// A string which shares its storage with the equal strings previously
+/// decoded on the same thread.
+///
+/// Use it instead of [`ItfString`](crate::ItfString) for variables holding
+/// values which repeat across states, such as addresses or enum constants.
+/// Interned strings are kept alive until [`InternedString::clear_pool`] is called.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InternedString(Arc<str>);
+
+impl InternedString {
+    /// Returns the interned copy of `s`, adding it to the pool if needed.
+    pub fn new(s: &str) -> Self {
+        POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+
+            match pool.get(s) {
+                Some(interned) => Self(Arc::clone(interned)),
+                None => {
+                    let interned = Arc::<str>::from(s);
+                    pool.insert(Arc::clone(&interned));
+                    Self(interned)
+                }
+            }
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether both strings share the same storage.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Empties the pool of the current thread. Strings decoded afterwards
+    /// do not share storage with those decoded before.
+    pub fn clear_pool() {
+        POOL.with(|pool| pool.borrow_mut().clear());
+    }
+}
+
+impl From<&str> for InternedString {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl Deref for InternedString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for InternedString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for InternedString {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl<'de> Deserialize<'de> for InternedString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct InternVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for InternVisitor {
+            type Value = InternedString;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E> {
+                Ok(InternedString::new(s))
+            }
+        }
+
+        deserializer.deserialize_str(InternVisitor)
+    }
+}
+
+impl Serialize for InternedString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ItfMap, ItfSet};
+
+    #[test]
+    fn shares_storage() {
+        #[derive(Deserialize)]
+        struct State {
+            owners: ItfSet<InternedString>,
+            balances: ItfMap<InternedString, i64>,
+        }
+
+        let states: Vec<State> = serde_json::from_str(
+            r##"[
+                { "owners": { "#set": ["alice"] }, "balances": { "#map": [["alice", 1]] } },
+                { "owners": { "#set": ["alice", "bob"] }, "balances": { "#map": [["bob", 2]] } }
+            ]"##,
+        )
+        .unwrap();
+
+        let alice = states[0].owners.iter().next().unwrap();
+        assert_eq!(alice.as_str(), "alice");
+
+        let (alice_key, _) = states[0].balances.get_key_value("alice").unwrap();
+        assert!(alice.ptr_eq(alice_key));
+
+        let alice_again = states[1].owners.get("alice").unwrap();
+        assert!(alice.ptr_eq(alice_again));
+
+        let (bob_key, _) = states[1].balances.get_key_value("bob").unwrap();
+        assert!(bob_key.ptr_eq(states[1].owners.get("bob").unwrap()));
+    }
+
+    #[test]
+    fn clear_pool() {
+        let before = InternedString::new("carol");
+        InternedString::clear_pool();
+        let after = InternedString::new("carol");
+
+        assert_eq!(before, after);
+        assert!(!before.ptr_eq(&after));
+    }
+
+    #[test]
+    fn serialize() {
+        let s = InternedString::from("dave");
+        assert_eq!(serde_json::to_string(&s).unwrap(), r#""dave""#);
+    }
+}
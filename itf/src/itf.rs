@@ -1,20 +1,63 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     fmt,
-    hash::Hash,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
     ops::{Deref, DerefMut},
 };
 
 use num_bigint::BigInt;
-use serde::{de::DeserializeOwned, Deserialize};
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed},
+    ser::SerializeStruct,
+    Deserialize, Serialize,
+};
+
+use crate::{
+    error::{located, raise},
+    util::serde::{
+        element::{Element, ElementSeed, Elements},
+        elements, struct_fields,
+        value::ValueDeserializer,
+    },
+    ItfError,
+};
 
 pub type ItfMap<K, V> = Itf<HashMap<K, V>>;
+
+/// A TLA+ set, decoded from a `#set` into a `HashSet`.
+///
+/// **Elements which are equal according to the `Eq` impl of `T` are collapsed
+/// into one**, even if the model checker considers them distinct, eg. because
+/// `T` only models some of the fields of a record. Use [`ItfSetVec`] to keep
+/// all the elements, [`ItfStrictSet`] to reject such sets, or [`ItfSetBy`]
+/// to compare elements by key.
 pub type ItfSet<T> = Itf<HashSet<T>>;
+pub type ItfBTreeMap<K, V> = Itf<BTreeMap<K, V>>;
+pub type ItfBTreeSet<T> = Itf<BTreeSet<T>>;
+pub type ItfList<T> = Itf<Vec<T>>;
+pub type ItfOption<T> = Itf<Option<T>>;
 pub type ItfTuple<T> = Itf<T>;
+/// A TLA+ integer, decoded from a bare number or a `#bigint`.
+///
+/// This is the recommended type for TLA+ `Int` values: Apalache emits them as
+/// a bare number when they fit in an `i64` and as a `#bigint` otherwise,
+/// possibly both in the same trace, and either form decodes to a `BigInt`.
+///
+/// The elements of a `#set`, `#map`, `#tup` or sequence can also be decoded
+/// into a plain `BigInt`, eg. `ItfSet<BigInt>`, without wrapping each of them,
+/// and such elements are encoded back as ITF integers.
 pub type ItfBigInt = Itf<BigInt>;
+pub type ItfNarrowInt = Itf<i128>;
+/// An integer known to fit in an `i64`, decoded from a bare number only.
+///
+/// Fails to decode as soon as the value takes the form of a `#bigint`;
+/// use [`ItfBigInt`] for unbounded integers.
 pub type ItfInt = i64;
 pub type ItfBool = bool;
 pub type ItfString = String;
+pub type ItfUnserializable = Itf<Unserializable>;
 
 #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Itf<T>(T);
@@ -37,10 +80,223 @@ where
     }
 }
 
+/// Compares a decoded big integer with a plain one.
+impl PartialEq<BigInt> for ItfBigInt {
+    fn eq(&self, other: &BigInt) -> bool {
+        self.0 == *other
+    }
+}
+
+/// Compares a decoded collection with a plain one, eg. an `ItfSet<T>` with a `HashSet<T>`.
+macro_rules! partial_eq_plain {
+    ($(<$($param:ident),+> $ty:ty where [$($bound:tt)+];)+) => {
+        $(
+            impl<$($param),+> PartialEq<$ty> for Itf<$ty>
+            where
+                $($bound)+
+            {
+                fn eq(&self, other: &$ty) -> bool {
+                    self.0 == *other
+                }
+            }
+        )+
+    };
+}
+
+partial_eq_plain! {
+    <T, S> HashSet<T, S> where [T: Eq + Hash, S: BuildHasher];
+    <K, V, S> HashMap<K, V, S> where [K: Eq + Hash, V: PartialEq, S: BuildHasher];
+    <T> BTreeSet<T> where [T: PartialEq];
+    <K, V> BTreeMap<K, V> where [K: PartialEq, V: PartialEq];
+    <T> Vec<T> where [T: PartialEq];
+    <T> VecDeque<T> where [T: PartialEq];
+}
+
 impl<T> Itf<T> {
+    /// Wraps a value, eg. to build constant fixtures.
+    ///
+    /// ```rust
+    /// use itf::{Itf, ItfTuple};
+    ///
+    /// const ORIGIN: ItfTuple<(i64, i64)> = Itf::new((0, 0));
+    ///
+    /// assert_eq!(ORIGIN.value(), (0, 0));
+    /// ```
+    pub const fn new(value: T) -> Self {
+        Itf(value)
+    }
+
     pub fn value(self) -> T {
         self.0
     }
+
+    /// Borrows the inner value, like `Deref` does, eg. an `ItfMap<K, V>` as a `HashMap<K, V>`.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use itf::ItfMap;
+    ///
+    /// let mut balances: ItfMap<String, i64> = [("alice".to_string(), 1)].into_iter().collect();
+    /// balances.inner_mut().insert("bob".to_string(), 2);
+    ///
+    /// let inner: &HashMap<String, i64> = balances.inner();
+    /// assert_eq!(inner.len(), 2);
+    /// ```
+    pub fn inner(&self) -> &T {
+        &self.0
+    }
+
+    /// Mutably borrows the inner value, like `DerefMut` does.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Transforms the inner value, keeping it wrapped.
+    ///
+    /// ```rust
+    /// use itf::{Itf, ItfBigInt};
+    ///
+    /// let n = ItfBigInt::from(42);
+    /// let s: Itf<String> = n.map(|n| n.to_string());
+    ///
+    /// assert_eq!(s.value(), "42");
+    /// ```
+    pub fn map<U, F>(self, f: F) -> Itf<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Itf(f(self.0))
+    }
+
+    /// Borrows the inner value, keeping it wrapped.
+    ///
+    /// ```rust
+    /// use itf::ItfBigInt;
+    ///
+    /// let n = ItfBigInt::from(42);
+    /// let bits = n.as_ref().map(|n| n.bits());
+    ///
+    /// assert_eq!(bits.value(), 6);
+    /// assert_eq!(n, ItfBigInt::from(42));
+    /// ```
+    pub fn as_ref(&self) -> Itf<&T> {
+        Itf(&self.0)
+    }
+
+    /// Encodes the value as ITF JSON, with its `#set`, `#map`, `#tup` and `#bigint` tags,
+    /// as `serde_json::to_value` does, eg. to splice it back into a raw trace.
+    ///
+    /// ```rust
+    /// use itf::ItfSet;
+    ///
+    /// let set: ItfSet<i64> = [1].into_iter().collect();
+    /// assert_eq!(set.to_itf_value()?, serde_json::json!({ "#set": [1] }));
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    ///
+    /// Returns an error if the inner value fails to serialize.
+    pub fn to_itf_value(&self) -> serde_json::Result<serde_json::Value>
+    where
+        Self: Serialize,
+    {
+        serde_json::to_value(self)
+    }
+}
+
+/// TLA+ set operators.
+impl<T> Itf<HashSet<T>>
+where
+    T: Eq + Hash,
+{
+    /// `x \in S`
+    pub fn contains<Q>(&self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.0.contains(x)
+    }
+
+    /// `S \subseteq T`
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.0.is_subset(&other.0)
+    }
+
+    /// `T \subseteq S`
+    pub fn is_superset(&self, other: &Self) -> bool {
+        self.0.is_superset(&other.0)
+    }
+
+    /// `S \union T`
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Itf(self.0.union(&other.0).cloned().collect())
+    }
+
+    /// `S \intersect T`
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Itf(self.0.intersection(&other.0).cloned().collect())
+    }
+
+    /// `S \ T`
+    pub fn difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        Itf(self.0.difference(&other.0).cloned().collect())
+    }
+}
+
+/// TLA+ sequences are indexed from 1, unlike Rust vectors.
+impl<T> Itf<Vec<T>> {
+    /// `s[i]`, with `i` in `1..Len(s)`
+    pub fn at(&self, i: usize) -> Result<&T, IndexOutOfDomain> {
+        i.checked_sub(1)
+            .and_then(|i| self.0.get(i))
+            .ok_or(IndexOutOfDomain {
+                index: i,
+                len: self.0.len(),
+            })
+    }
+
+    /// `Head(s)`
+    pub fn head(&self) -> Result<&T, IndexOutOfDomain> {
+        self.at(1)
+    }
+
+    /// `Tail(s)`
+    pub fn tail(&self) -> Result<Self, IndexOutOfDomain>
+    where
+        T: Clone,
+    {
+        match self.0.split_first() {
+            Some((_, tail)) => Ok(Itf(tail.to_vec())),
+            None => Err(IndexOutOfDomain { index: 1, len: 0 }),
+        }
+    }
+
+    /// `Len(s)`
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// `s = <<>>`
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A sequence was indexed outside of its domain `1..len`.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("index {index} is not in the domain `1..{len}` of the sequence")]
+pub struct IndexOutOfDomain {
+    pub index: usize,
+    pub len: usize,
 }
 
 impl<T> Deref for Itf<T> {
@@ -57,25 +313,182 @@ impl<T> DerefMut for Itf<T> {
     }
 }
 
-impl<'de, T> Deserialize<'de> for Itf<HashSet<T>>
+impl<T> IntoIterator for Itf<T>
 where
-    T: Eq + Hash + Deserialize<'de>,
+    T: IntoIterator,
+{
+    type Item = T::Item;
+    type IntoIter = T::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Itf<T>
+where
+    &'a T: IntoIterator,
+{
+    type Item = <&'a T as IntoIterator>::Item;
+    type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Itf<T>
+where
+    &'a mut T: IntoIterator,
+{
+    type Item = <&'a mut T as IntoIterator>::Item;
+    type IntoIter = <&'a mut T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T, A> FromIterator<A> for Itf<T>
+where
+    T: FromIterator<A>,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+    {
+        Itf(T::from_iter(iter))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct Set<T> {
+    #[serde(rename = "#set", deserialize_with = "set_elements")]
+    set: Vec<T>,
+}
+
+fn set_elements<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    elements::deserialize("#set", deserializer)
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "K: Deserialize<'de>, V: Deserialize<'de>"))]
+struct Map<K, V> {
+    #[serde(rename = "#map", deserialize_with = "map_elements")]
+    elements: Vec<Entry<K, V>>,
+}
+
+fn map_elements<'de, D, K, V>(deserializer: D) -> Result<Vec<Entry<K, V>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    elements::deserialize("#map", deserializer)
+}
+
+/// A key-value pair of a map, ie. a sequence of two elements.
+struct Entry<K, V>(K, V);
+
+impl<'de, K, V> Deserialize<'de> for Entry<K, V>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        pub struct Set<T> {
-            #[serde(rename = "#set")]
-            set: Vec<T>,
+        struct EntryVisitor<K, V>(PhantomData<(K, V)>);
+
+        impl<'de, K, V> serde::de::Visitor<'de> for EntryVisitor<K, V>
+        where
+            K: Deserialize<'de>,
+            V: Deserialize<'de>,
+        {
+            type Value = Entry<K, V>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a key-value pair")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                use serde::de::Error;
+
+                let key = seq
+                    .next_element_seed(ElementSeed::new())
+                    .map_err(|e| located(0, e))?
+                    .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+
+                let value = seq
+                    .next_element_seed(ElementSeed::new())
+                    .map_err(|e| located(1, e))?
+                    .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+
+                if seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+                    return Err(A::Error::invalid_length(3, &self));
+                }
+
+                Ok(Entry(key, value))
+            }
         }
 
+        deserializer.deserialize_seq(EntryVisitor(PhantomData))
+    }
+}
+
+/// Serializes the key-value pairs of a map as a sequence of pairs.
+struct Entries<I>(I);
+
+impl<'a, I, K, V> Serialize for Entries<I>
+where
+    I: Iterator<Item = (&'a K, &'a V)> + Clone,
+    K: Serialize + 'a,
+    V: Serialize + 'a,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.clone().map(|(k, v)| (Element(k), Element(v))))
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Itf<HashSet<T>>
+where
+    T: Eq + Hash + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
         let set = Set::<T>::deserialize(deserializer)?;
         Ok(Self(set.set.into_iter().collect()))
     }
 }
 
+impl<T> Serialize for Itf<HashSet<T>>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Set", 1)?;
+        s.serialize_field("#set", &Elements(self.0.iter()))?;
+        s.end()
+    }
+}
+
 impl<'de, K, V> Deserialize<'de> for Itf<HashMap<K, V>>
 where
     K: Eq + Hash + DeserializeOwned,
@@ -85,131 +498,2533 @@ where
     where
         D: serde::Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        pub struct Map<K, V> {
-            #[serde(rename = "#map")]
-            elements: Vec<(K, V)>,
-        }
-
         let map = Map::<K, V>::deserialize(deserializer)?;
-        Ok(Self(map.elements.into_iter().collect()))
+        Ok(Self(
+            map.elements.into_iter().map(|Entry(k, v)| (k, v)).collect(),
+        ))
     }
 }
 
-impl<'de> Deserialize<'de> for Itf<BigInt> {
+impl<K, V> Serialize for Itf<HashMap<K, V>>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Map", 1)?;
+        s.serialize_field("#map", &Entries(self.0.iter()))?;
+        s.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Itf<BTreeSet<T>>
+where
+    T: Ord + Deserialize<'de>,
+{
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        struct BI {
-            #[serde(rename = "#bigint", with = "crate::util::serde::display_from_str")]
-            value: num_bigint::BigInt,
-        }
-
-        #[derive(Deserialize)]
-        #[serde(untagged)]
-        enum IntOrBigInt {
-            Int(i64),
-            BigInt(BI),
-        }
+        let set = Set::<T>::deserialize(deserializer)?;
+        Ok(Self(set.set.into_iter().collect()))
+    }
+}
 
-        IntOrBigInt::deserialize(deserializer)
-            .map(|ib| match ib {
-                IntOrBigInt::Int(n) => BigInt::from(n),
-                IntOrBigInt::BigInt(b) => b.value,
-            })
-            .map(Itf)
+impl<T> Serialize for Itf<BTreeSet<T>>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Set", 1)?;
+        s.serialize_field("#set", &Elements(self.0.iter()))?;
+        s.end()
     }
 }
 
-#[derive(Deserialize)]
-struct Tup {
-    #[serde(rename = "#tup")]
-    elements: Vec<serde_json::Value>,
+impl<'de, K, V> Deserialize<'de> for Itf<BTreeMap<K, V>>
+where
+    K: Ord + DeserializeOwned,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let map = Map::<K, V>::deserialize(deserializer)?;
+        Ok(Self(
+            map.elements.into_iter().map(|Entry(k, v)| (k, v)).collect(),
+        ))
+    }
 }
 
-macro_rules! deserialize_itf_tuple {
-    ($len:literal, $($n:literal $ty:ident)+) => {
-        impl<'de, $($ty ,)+> Deserialize<'de> for Itf<($($ty ,)+)>
-        where
-            $($ty: DeserializeOwned,)+
-        {
-            #[allow(non_snake_case)]
-            fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
-            where
-                De: serde::Deserializer<'de>,
-            {
-                let mut elements = Tup::deserialize(deserializer).map(|t| t.elements)?;
+impl<K, V> Serialize for Itf<BTreeMap<K, V>>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Map", 1)?;
+        s.serialize_field("#map", &Entries(self.0.iter()))?;
+        s.end()
+    }
+}
 
-                if elements.len() != $len {
-                    return Err(serde::de::Error::custom(format_args!(
-                        "expected tuple with {} elements but found {}", $len, elements.len()
-                    )));
-                }
+/// A TLA+ set, decoded from a `#set` into a persistent `im::HashSet`,
+/// which clones in constant time, eg. to keep snapshots of large states.
+#[cfg(feature = "im")]
+pub type ItfImSet<T> = Itf<im::HashSet<T>>;
 
-                $(
-                    let $ty: $ty = serde_json::from_value(std::mem::take(&mut elements[$n]))
-                        .map_err(|e| serde::de::Error::custom(e))?;
-                )+
+/// A TLA+ function, decoded from a `#map` into a persistent `im::HashMap`,
+/// which clones in constant time, eg. to keep snapshots of large states.
+#[cfg(feature = "im")]
+pub type ItfImMap<K, V> = Itf<im::HashMap<K, V>>;
 
-                Ok(Itf(($($ty,)+)))
-            }
-        }
-    };
+#[cfg(feature = "im")]
+impl<'de, T> Deserialize<'de> for Itf<im::HashSet<T>>
+where
+    T: Clone + Eq + Hash + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let set = Set::<T>::deserialize(deserializer)?;
+        Ok(Self(set.set.into_iter().collect()))
+    }
 }
 
-deserialize_itf_tuple!(2,  0 A 1 B);
-deserialize_itf_tuple!(3,  0 A 1 B 2 C);
-deserialize_itf_tuple!(4,  0 A 1 B 2 C 3 D);
-deserialize_itf_tuple!(5,  0 A 1 B 2 C 3 D 4 E);
-deserialize_itf_tuple!(6,  0 A 1 B 2 C 3 D 4 E 5 F);
-deserialize_itf_tuple!(7,  0 A 1 B 2 C 3 D 4 E 5 F 6 G);
-// deserialize_itf_tuple!(8,  0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H);
-// deserialize_itf_tuple!(9,  0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I);
-// deserialize_itf_tuple!(10, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J);
-// deserialize_itf_tuple!(11, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K);
-// deserialize_itf_tuple!(12, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L);
+#[cfg(feature = "im")]
+impl<T> Serialize for Itf<im::HashSet<T>>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let elements = self.0.iter().collect::<Vec<_>>();
 
-impl<T> From<T> for ItfBigInt
+        let mut s = serializer.serialize_struct("Set", 1)?;
+        s.serialize_field("#set", &Elements(elements.iter().copied()))?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "im")]
+impl<'de, K, V> Deserialize<'de> for Itf<im::HashMap<K, V>>
 where
-    BigInt: From<T>,
+    K: Clone + Eq + Hash + DeserializeOwned,
+    V: Clone + Deserialize<'de>,
 {
-    fn from(t: T) -> Self {
-        Itf(BigInt::from(t))
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let map = Map::<K, V>::deserialize(deserializer)?;
+        Ok(Self(
+            map.elements.into_iter().map(|Entry(k, v)| (k, v)).collect(),
+        ))
     }
 }
 
-#[cfg(test)]
+#[cfg(feature = "im")]
+impl<K, V> Serialize for Itf<im::HashMap<K, V>>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let entries = self.0.iter().collect::<Vec<_>>();
+
+        let mut s = serializer.serialize_struct("Map", 1)?;
+        s.serialize_field("#map", &Entries(entries.into_iter()))?;
+        s.end()
+    }
+}
+
+/// TLA+ sequences are encoded as plain JSON arrays,
+/// as opposed to tuples which are wrapped in `#tup`.
+impl<'de, T> Deserialize<'de> for Itf<Vec<T>>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        elements::deserialize("", deserializer).map(Self)
+    }
+}
+
+/// A TLA+ sequence which models a FIFO queue, decoded like an [`ItfList`].
+impl<'de, T> Deserialize<'de> for Itf<VecDeque<T>>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        elements::deserialize::<_, T>("", deserializer).map(|elements| Self(elements.into()))
+    }
+}
+
+/// An optional value, which decodes to `None` when:
+///
+/// - the variable or record field is absent, provided that the field is marked
+///   with `#[serde(default)]` in a type deriving `Deserialize`, as serde requires
+///   for absent fields; no attribute is needed in a type deriving `ItfState`,
+/// - it is the `{ "#unserializable": "None" }` marker,
+/// - it is the `None` variant of a Quint `Option`, ie. `{ "tag": "None", "value": ... }`.
+///
+/// The `Some` variant of a Quint `Option` decodes to its value, and any other value
+/// decodes to `Some` through the `Deserialize` impl of `T`.
+///
+/// A field which is present but `null` is rejected, rather than treated as absent,
+/// as ITF never emits `null`: it is a sign of a trace which was not produced
+/// by a model checker.
+impl<'de, T> Deserialize<'de> for Itf<Option<T>>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{value, Error, Unexpected};
+        use serde_json::Value;
+
+        struct OptionVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> serde::de::Visitor<'de> for OptionVisitor<T>
+        where
+            T: DeserializeOwned,
+        {
+            type Value = Option<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an optional value")
+            }
+
+            /// An absent field, which only the derived `ItfState` impls report
+            /// with `deserialize_any`, as opposed to a `null`, reported as `()`.
+            fn visit_none<E>(self) -> Result<Self::Value, E> {
+                Ok(None)
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Err(E::invalid_type(Unexpected::Unit, &"an ITF value, not null"))
+            }
+
+            fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                decode_some(Value::from(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                decode_some(Value::from(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                decode_some(Value::from(v))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                decode_some(Value::from(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                decode_some(Value::from(v))
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                decode_some(Value::deserialize(value::SeqAccessDeserializer::new(seq))?)
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut value = Value::deserialize(value::MapAccessDeserializer::new(map))?;
+
+                if let Value::Object(obj) = &mut value {
+                    if obj.len() == 1 && obj.get("#unserializable") == Some(&"None".into()) {
+                        return Ok(None);
+                    }
+
+                    if obj.len() == 2 && obj.contains_key("value") {
+                        match obj.get("tag").and_then(Value::as_str) {
+                            Some("None") => return Ok(None),
+                            Some("Some") => value = obj.remove("value").unwrap_or_default(),
+                            _ => {}
+                        }
+                    }
+                }
+
+                decode_some(value)
+            }
+        }
+
+        fn decode_some<T, E>(value: Value) -> Result<Option<T>, E>
+        where
+            T: DeserializeOwned,
+            E: Error,
+        {
+            T::deserialize(ValueDeserializer::new(&value)).map(Some)
+        }
+
+        deserializer
+            .deserialize_any(OptionVisitor(PhantomData))
+            .map(Self)
+    }
+}
+
+/// Parses a big integer in decimal, or in hexadecimal, octal or binary
+/// when prefixed with `0x`, `0o` or `0b`, as emitted by some producers.
+fn parse_bigint(s: &str) -> Option<BigInt> {
+    let (negative, unsigned) = match s.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, s),
+    };
+
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..]),
+        Some("0o" | "0O") => (8, &unsigned[2..]),
+        Some("0b" | "0B") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    // `parse_bytes` and `parse` would accept a second sign or underscores
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    let n = BigInt::parse_bytes(digits.as_bytes(), radix)?;
+    Some(if negative { -n } else { n })
+}
+
+impl<'de> Deserialize<'de> for Itf<BigInt> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct BigIntVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BigIntVisitor {
+            type Value = BigInt;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an integer or a `#bigint`")
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Self::Value, E> {
+                Ok(BigInt::from(n))
+            }
+
+            fn visit_u64<E>(self, n: u64) -> Result<Self::Value, E> {
+                Ok(BigInt::from(n))
+            }
+
+            // Some non-Apalache producers emit big integers as bare strings
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                parse_bigint(s).ok_or_else(|| raise(ItfError::BigIntParse(s.to_string())))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut value = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "#bigint" {
+                        let s = map.next_value::<String>()?;
+                        value = Some(BigIntVisitor.visit_str(&s)?);
+                    } else {
+                        map.next_value::<serde::de::IgnoredAny>()?;
+                    }
+                }
+
+                value.ok_or_else(|| serde::de::Error::missing_field("#bigint"))
+            }
+        }
+
+        deserializer.deserialize_any(BigIntVisitor).map(Itf)
+    }
+}
+
+/// Like Apalache, emits a bare number when the value fits in an `i64`,
+/// and a `#bigint` otherwise.
+impl Serialize for Itf<BigInt> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if let Ok(n) = i64::try_from(&self.0) {
+            return serializer.serialize_i64(n);
+        }
+
+        let mut s = serializer.serialize_struct("BigInt", 1)?;
+        s.serialize_field("#bigint", &self.0.to_string())?;
+        s.end()
+    }
+}
+
+/// Decodes a bare integer or a `#bigint` into an `i128`, without allocating,
+/// for models whose integers are known to fit in 128 bits.
+///
+/// Fails to decode integers which do not fit in an `i128`.
+impl<'de> Deserialize<'de> for Itf<i128> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct NarrowIntVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for NarrowIntVisitor {
+            type Value = i128;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an integer or a `#bigint` which fits in an `i128`")
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Self::Value, E> {
+                Ok(i128::from(n))
+            }
+
+            fn visit_u64<E>(self, n: u64) -> Result<Self::Value, E> {
+                Ok(i128::from(n))
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match parse_bigint(s) {
+                    Some(n) => {
+                        i128::try_from(n).map_err(|_| raise(ItfError::IntOverflow(s.to_string())))
+                    }
+                    None => Err(raise(ItfError::BigIntParse(s.to_string()))),
+                }
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut value = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "#bigint" {
+                        let s = map.next_value::<String>()?;
+                        value = Some(NarrowIntVisitor.visit_str(&s)?);
+                    } else {
+                        map.next_value::<serde::de::IgnoredAny>()?;
+                    }
+                }
+
+                value.ok_or_else(|| serde::de::Error::missing_field("#bigint"))
+            }
+        }
+
+        deserializer.deserialize_any(NarrowIntVisitor).map(Itf)
+    }
+}
+
+/// Like for `ItfBigInt`, emits a bare number when the value fits in an `i64`,
+/// and a `#bigint` otherwise.
+impl Serialize for Itf<i128> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if let Ok(n) = i64::try_from(self.0) {
+            return serializer.serialize_i64(n);
+        }
+
+        let mut s = serializer.serialize_struct("BigInt", 1)?;
+        s.serialize_field("#bigint", &self.0.to_string())?;
+        s.end()
+    }
+}
+
+/// A real number, decoded from a JSON number, a string or a `#bigint`, as
+/// emitted by some tools for specs which model reals. Being totally ordered,
+/// it can be used as the key of an `ItfMap` or `ItfBTreeMap`.
+///
+/// The value is rounded to the nearest `f64`, so decimals such as `0.1`
+/// and integers beyond 2^53 are not represented exactly. Non-finite values,
+/// such as `"NaN"` or `"inf"`, fail to decode.
+#[cfg(feature = "ordered-float")]
+pub type ItfReal = Itf<ordered_float::OrderedFloat<f64>>;
+
+#[cfg(feature = "ordered-float")]
+impl<'de> Deserialize<'de> for Itf<ordered_float::OrderedFloat<f64>> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct RealVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for RealVisitor {
+            type Value = f64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a finite number, as a number, a string or a `#bigint`")
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Self::Value, E> {
+                Ok(n as f64)
+            }
+
+            fn visit_u64<E>(self, n: u64) -> Result<Self::Value, E> {
+                Ok(n as f64)
+            }
+
+            fn visit_f64<E>(self, n: f64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                if n.is_finite() {
+                    Ok(n)
+                } else {
+                    Err(E::custom(format!("real `{n}` is not finite")))
+                }
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let n = s
+                    .parse::<f64>()
+                    .map_err(|_| E::custom(format!("invalid real `{s}`")))?;
+
+                self.visit_f64(n)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut value = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "#bigint" {
+                        let s = map.next_value::<String>()?;
+                        let n = parse_bigint(&s)
+                            .ok_or_else(|| raise::<A::Error>(ItfError::BigIntParse(s)))?;
+                        value = Some(RealVisitor.visit_str(&n.to_string())?);
+                    } else {
+                        map.next_value::<serde::de::IgnoredAny>()?;
+                    }
+                }
+
+                value.ok_or_else(|| serde::de::Error::missing_field("#bigint"))
+            }
+        }
+
+        deserializer
+            .deserialize_any(RealVisitor)
+            .map(|n| Itf(ordered_float::OrderedFloat(n)))
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl Serialize for Itf<ordered_float::OrderedFloat<f64>> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_f64(self.0 .0)
+    }
+}
+
+/// An instant, decoded from a bare integer or a `#bigint` counting nanoseconds
+/// since the Unix epoch, for models which encode times as integers.
+#[cfg(feature = "chrono")]
+pub type ItfInstant = Itf<chrono::DateTime<chrono::Utc>>;
+
+/// Fails to decode negative integers, and integers which do not fit in an `i64`,
+/// ie. instants after 2262.
+#[cfg(feature = "chrono")]
+impl<'de> Deserialize<'de> for Itf<chrono::DateTime<chrono::Utc>> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let nanos = ItfBigInt::deserialize(deserializer)?.value();
+
+        if nanos.sign() == num_bigint::Sign::Minus {
+            return Err(D::Error::custom(format!(
+                "timestamp `{nanos}` is before the Unix epoch"
+            )));
+        }
+
+        match i64::try_from(&nanos) {
+            Ok(nanos) => Ok(Itf(chrono::TimeZone::timestamp_nanos(&chrono::Utc, nanos))),
+            Err(_) => Err(D::Error::custom(format!(
+                "timestamp `{nanos}` is out of range"
+            ))),
+        }
+    }
+}
+
+/// Emits the number of nanoseconds since the Unix epoch as a bare number.
+#[cfg(feature = "chrono")]
+impl Serialize for Itf<chrono::DateTime<chrono::Utc>> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+
+        match self.0.timestamp_nanos_opt() {
+            Some(nanos) => serializer.serialize_i64(nanos),
+            None => Err(S::Error::custom(format!(
+                "instant `{}` is out of range",
+                self.0
+            ))),
+        }
+    }
+}
+
+/// A value which Apalache could not serialize, along with its string rendering.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Unserializable(String);
+
+impl Unserializable {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Unserializable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<'de> Deserialize<'de> for Itf<Unserializable> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Unser {
+            #[serde(rename = "#unserializable")]
+            value: String,
+        }
+
+        let unser = Unser::deserialize(deserializer)?;
+        Ok(Self(Unserializable(unser.value)))
+    }
+}
+
+/// A TLA+ record, ie. a JSON object mapping field names to ITF values,
+/// decoded into the struct `T`.
+///
+/// Unlike a plain `#[derive(Deserialize)]` struct, fields of the record
+/// which are not declared in `T` are rejected. To accept them, use
+/// `ItfRecord<T, AllowUnknownFields>`, which passes them on to `T`:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use itf::{AllowUnknownFields, ItfRecord, ItfString};
+///
+/// #[derive(Deserialize)]
+/// struct Account {
+///     owner: ItfString,
+/// }
+///
+/// let json = serde_json::json!({ "owner": "alice", "balance": 5 });
+///
+/// assert!(serde_json::from_value::<ItfRecord<Account>>(json.clone()).is_err());
+///
+/// let account: ItfRecord<Account, AllowUnknownFields> = serde_json::from_value(json).unwrap();
+/// assert_eq!(account.owner, "alice");
+/// ```
+///
+/// The object is always decoded as a record, even if one of its keys
+/// happens to be an ITF tag such as `#tup` or `#set`.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItfRecord<T, F = DenyUnknownFields>(T, PhantomData<F>);
+
+/// Whether an [`ItfRecord`] accepts the fields which its struct does not declare.
+pub trait UnknownFields {
+    const ALLOW: bool;
+}
+
+/// Makes an [`ItfRecord`] reject the fields which its struct does not declare.
+/// This is the default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DenyUnknownFields;
+
+impl UnknownFields for DenyUnknownFields {
+    const ALLOW: bool = false;
+}
+
+/// Makes an [`ItfRecord`] pass the fields which its struct does not declare on
+/// to the struct, which ignores them unless it has `#[serde(deny_unknown_fields)]`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AllowUnknownFields;
+
+impl UnknownFields for AllowUnknownFields {
+    const ALLOW: bool = true;
+}
+
+impl<T, F> fmt::Debug for ItfRecord<T, F>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T, F> ItfRecord<T, F> {
+    pub fn value(self) -> T {
+        self.0
+    }
+}
+
+impl<T, F> Deref for ItfRecord<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T, F> DerefMut for ItfRecord<T, F> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, T, F> Deserialize<'de> for ItfRecord<T, F>
+where
+    T: DeserializeOwned,
+    F: UnknownFields,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let record = serde_json::Map::deserialize(deserializer)?;
+
+        if let Some(fields) = struct_fields::of::<T>().filter(|_| !F::ALLOW) {
+            if let Some(unknown) = record.keys().find(|k| !fields.contains(&k.as_str())) {
+                return Err(serde::de::Error::unknown_field(unknown, fields));
+            }
+        }
+
+        T::deserialize(ValueDeserializer::new(&serde_json::Value::Object(record)))
+            .map(|value| ItfRecord(value, PhantomData))
+    }
+}
+
+/// A state or record, decoded into the struct `T` from only the variables or
+/// fields which `T` declares, so that the others are neither decoded, and thus
+/// cannot fail to, nor rejected, eg. by `#[serde(deny_unknown_fields)]`.
+///
+/// This is the opposite of [`ItfRecord`], for modeling a few variables of a large state:
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use itf::{trace_from_value, ItfBigInt, PartialState, Trace};
+///
+/// #[derive(Deserialize)]
+/// #[serde(deny_unknown_fields)]
+/// struct Supply {
+///     supply: ItfBigInt,
+/// }
+///
+/// let json = serde_json::json!({
+///     "#meta": {},
+///     "states": [{ "supply": 1, "ledger": { "#unknown": [] } }],
+/// });
+///
+/// let trace: Trace<PartialState<Supply>> = trace_from_value(json).unwrap();
+/// assert_eq!(trace.states[0].value.supply, ItfBigInt::from(1));
+/// ```
+///
+/// If `T` does not deserialize as a plain struct, eg. because it has a
+/// `#[serde(flatten)]` field, all the variables are passed to it.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartialState<T>(T);
+
+impl<T> fmt::Debug for PartialState<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T> PartialState<T> {
+    pub fn value(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for PartialState<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PartialState<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, T> Deserialize<'de> for PartialState<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut vars = serde_json::Map::deserialize(deserializer)?;
+
+        if let Some(fields) = struct_fields::of::<T>() {
+            vars.retain(|k, _| fields.contains(&k.as_str()));
+        }
+
+        T::deserialize(ValueDeserializer::new(&serde_json::Value::Object(vars))).map(PartialState)
+    }
+}
+
+impl<T> Serialize for PartialState<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+/// A TLA+ function, decoded from a `#map` like an [`ItfMap`], with accessors
+/// which read like function application.
+#[derive(Clone)]
+pub struct ItfFunction<K, V>(ItfMap<K, V>);
+
+impl<K, V> PartialEq for ItfFunction<K, V>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K, V> Eq for ItfFunction<K, V>
+where
+    K: Eq + Hash,
+    V: Eq,
+{
+}
+
+impl<K, V> Default for ItfFunction<K, V> {
+    fn default() -> Self {
+        Self(Itf(HashMap::new()))
+    }
+}
+
+impl<K, V> fmt::Debug for ItfFunction<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<K, V> ItfFunction<K, V>
+where
+    K: Eq + Hash,
+{
+    /// Applies the function to `k`, or returns `None` if `k` is not in its domain.
+    pub fn apply<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.0.get(k)
+    }
+
+    /// Applies the function to `k`, or returns `default` if `k` is not in its domain.
+    pub fn get_or<'a, Q>(&'a self, k: &Q, default: &'a V) -> &'a V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.apply(k).unwrap_or(default)
+    }
+
+    pub fn value(self) -> HashMap<K, V> {
+        self.0.value()
+    }
+}
+
+impl<K, V> Deref for ItfFunction<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for ItfFunction<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for ItfFunction<K, V>
+where
+    K: Eq + Hash,
+{
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for ItfFunction<K, V>
+where
+    K: Eq + Hash + DeserializeOwned,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        ItfMap::deserialize(deserializer).map(Self)
+    }
+}
+
+impl<K, V> Serialize for ItfFunction<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+/// A TLA+ set, decoded from a `#set` into a `Vec` which keeps all its elements,
+/// in the order they appear in the trace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ItfSetVec<T>(Vec<T>);
+
+impl<T> ItfSetVec<T> {
+    pub fn value(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> Deref for ItfSetVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ItfSetVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ItfSetVec<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let set = Set::<T>::deserialize(deserializer)?;
+        Ok(Self(set.set))
+    }
+}
+
+impl<T> Serialize for ItfSetVec<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Set", 1)?;
+        s.serialize_field("#set", &Elements(self.0.iter()))?;
+        s.end()
+    }
+}
+
+/// A TLA+ function, decoded from a `#map` into a `Vec` of its key-value pairs,
+/// in the order they appear in the trace.
+///
+/// Unlike [`ItfMap`], its keys need not be `Hash` nor `Ord`, and entries with
+/// equal keys are all kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ItfMapVec<K, V>(Vec<(K, V)>);
+
+impl<K, V> ItfMapVec<K, V> {
+    /// Returns the value of the first entry whose key is `k`, if any.
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.0
+            .iter()
+            .find(|(key, _)| key.borrow() == k)
+            .map(|(_, v)| v)
+    }
+
+    /// Iterates over the key-value pairs, in the order they appear in the trace.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn value(self) -> Vec<(K, V)> {
+        self.0
+    }
+}
+
+impl<K, V> Deref for ItfMapVec<K, V> {
+    type Target = Vec<(K, V)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for ItfMapVec<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for ItfMapVec<K, V>
+where
+    K: DeserializeOwned,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let map = Map::<K, V>::deserialize(deserializer)?;
+        Ok(Self(
+            map.elements.into_iter().map(|Entry(k, v)| (k, v)).collect(),
+        ))
+    }
+}
+
+impl<K, V> Serialize for ItfMapVec<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Map", 1)?;
+        s.serialize_field("#map", &Entries(self.0.iter().map(|(k, v)| (k, v))))?;
+        s.end()
+    }
+}
+
+/// A TLA+ set, decoded from a `#set` into a `HashSet`, which fails to decode
+/// if two of its elements are equal according to the `Eq` impl of `T`.
+#[derive(Clone, Debug)]
+pub struct ItfStrictSet<T>(ItfSet<T>);
+
+impl<T> Default for ItfStrictSet<T> {
+    fn default() -> Self {
+        Self(Itf(HashSet::new()))
+    }
+}
+
+impl<T> PartialEq for ItfStrictSet<T>
+where
+    T: Eq + Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for ItfStrictSet<T> where T: Eq + Hash {}
+
+impl<T> ItfStrictSet<T> {
+    pub fn value(self) -> HashSet<T> {
+        self.0.value()
+    }
+}
+
+impl<T> Deref for ItfStrictSet<T> {
+    type Target = HashSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ItfStrictSet<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ItfStrictSet<T>
+where
+    T: Eq + Hash + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let elements = Set::<T>::deserialize(deserializer)?.set;
+        let mut set = HashSet::with_capacity(elements.len());
+
+        for (i, element) in elements.into_iter().enumerate() {
+            if !set.insert(element) {
+                return Err(located(
+                    ("#set", i),
+                    raise::<D::Error>(ItfError::DuplicateElement),
+                ));
+            }
+        }
+
+        Ok(Self(Itf(set)))
+    }
+}
+
+impl<T> Serialize for ItfStrictSet<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Projects the elements of a set onto the key which identifies them,
+/// for [`ItfSetBy`] and [`ItfBTreeSetBy`].
+///
+/// ```
+/// # use itf::SetKey;
+/// struct Account { id: u64, balance: i64 }
+///
+/// struct ById;
+///
+/// impl SetKey<Account> for ById {
+///     type Key = u64;
+///
+///     fn key(account: &Account) -> u64 {
+///         account.id
+///     }
+/// }
+/// ```
+pub trait SetKey<T> {
+    type Key;
+
+    fn key(element: &T) -> Self::Key;
+}
+
+/// A TLA+ set, decoded from a `#set` into a `HashMap` from the key of each
+/// element, as projected by `F`, to the element.
+///
+/// Use it when the set is made of records which are identified by some of
+/// their fields only. Elements with the same key are collapsed into the
+/// first one, just as a `HashSet` keeps the first of equal elements, and two
+/// sets are equal if they have the same keys.
+/// Its elements are iterated in arbitrary order; use [`ItfBTreeSetBy`] for
+/// a deterministic order.
+pub struct ItfSetBy<T, F>
+where
+    F: SetKey<T>,
+{
+    elements: HashMap<F::Key, T>,
+    key: PhantomData<F>,
+}
+
+impl<T, F> ItfSetBy<T, F>
+where
+    F: SetKey<T>,
+    F::Key: Eq + Hash,
+{
+    /// Returns whether the set has an element with the same key as `element`.
+    pub fn contains(&self, element: &T) -> bool {
+        self.elements.contains_key(&F::key(element))
+    }
+
+    pub fn value(self) -> HashMap<F::Key, T> {
+        self.elements
+    }
+}
+
+impl<T, F> FromIterator<T> for ItfSetBy<T, F>
+where
+    F: SetKey<T>,
+    F::Key: Eq + Hash,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elements = HashMap::new();
+
+        for element in iter {
+            elements.entry(F::key(&element)).or_insert(element);
+        }
+
+        Self {
+            elements,
+            key: PhantomData,
+        }
+    }
+}
+
+/// A TLA+ set, decoded from a `#set` into a `BTreeMap` from the key of each
+/// element, as projected by `F`, to the element.
+///
+/// It behaves like [`ItfSetBy`], but its elements are iterated in the order
+/// of their keys.
+pub struct ItfBTreeSetBy<T, F>
+where
+    F: SetKey<T>,
+{
+    elements: BTreeMap<F::Key, T>,
+    key: PhantomData<F>,
+}
+
+impl<T, F> ItfBTreeSetBy<T, F>
+where
+    F: SetKey<T>,
+    F::Key: Ord,
+{
+    /// Returns whether the set has an element with the same key as `element`.
+    pub fn contains(&self, element: &T) -> bool {
+        self.elements.contains_key(&F::key(element))
+    }
+
+    pub fn value(self) -> BTreeMap<F::Key, T> {
+        self.elements
+    }
+}
+
+impl<T, F> FromIterator<T> for ItfBTreeSetBy<T, F>
+where
+    F: SetKey<T>,
+    F::Key: Ord,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elements = BTreeMap::new();
+
+        for element in iter {
+            elements.entry(F::key(&element)).or_insert(element);
+        }
+
+        Self {
+            elements,
+            key: PhantomData,
+        }
+    }
+}
+
+macro_rules! impl_set_by {
+    ($set:ident, $map:ident, $($bound:tt)+) => {
+        impl<T, F> Clone for $set<T, F>
+        where
+            T: Clone,
+            F: SetKey<T>,
+            F::Key: Clone,
+        {
+            fn clone(&self) -> Self {
+                Self {
+                    elements: self.elements.clone(),
+                    key: PhantomData,
+                }
+            }
+        }
+
+        impl<T, F> fmt::Debug for $set<T, F>
+        where
+            T: fmt::Debug,
+            F: SetKey<T>,
+        {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_set().entries(self.elements.values()).finish()
+            }
+        }
+
+        impl<T, F> Default for $set<T, F>
+        where
+            F: SetKey<T>,
+        {
+            fn default() -> Self {
+                Self {
+                    elements: $map::new(),
+                    key: PhantomData,
+                }
+            }
+        }
+
+        impl<T, F> PartialEq for $set<T, F>
+        where
+            F: SetKey<T>,
+            F::Key: $($bound)+,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                self.elements.len() == other.elements.len()
+                    && self.elements.keys().all(|k| other.elements.contains_key(k))
+            }
+        }
+
+        impl<T, F> Eq for $set<T, F>
+        where
+            F: SetKey<T>,
+            F::Key: $($bound)+,
+        {
+        }
+
+        impl<T, F> Deref for $set<T, F>
+        where
+            F: SetKey<T>,
+        {
+            type Target = $map<F::Key, T>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.elements
+            }
+        }
+
+        impl<'de, T, F> Deserialize<'de> for $set<T, F>
+        where
+            T: Deserialize<'de>,
+            F: SetKey<T>,
+            F::Key: $($bound)+,
+        {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                Ok(Set::<T>::deserialize(deserializer)?.set.into_iter().collect())
+            }
+        }
+
+        impl<T, F> Serialize for $set<T, F>
+        where
+            T: Serialize,
+            F: SetKey<T>,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                let elements = self.elements.values().collect::<Vec<_>>();
+
+                let mut s = serializer.serialize_struct("Set", 1)?;
+                s.serialize_field("#set", &Elements(elements.iter().copied()))?;
+                s.end()
+            }
+        }
+    };
+}
+
+impl_set_by!(ItfSetBy, HashMap, Eq + Hash);
+impl_set_by!(ItfBTreeSetBy, BTreeMap, Ord);
+
+/// A TLA+ function with string keys, decoded either from a `#map` or from
+/// a bare JSON object, as emitted by some producers for interoperability.
+///
+/// An object tagged with another ITF tag, such as `#set` or `#tup`, is not
+/// a bare object and fails to decode. It is encoded as a `#map`.
+#[derive(Clone, Debug)]
+pub struct ItfObjectMap<V>(ItfMap<String, V>);
+
+impl<V> Default for ItfObjectMap<V> {
+    fn default() -> Self {
+        Self(Itf(HashMap::new()))
+    }
+}
+
+impl<V> PartialEq for ItfObjectMap<V>
+where
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<V> Eq for ItfObjectMap<V> where V: Eq {}
+
+impl<V> ItfObjectMap<V> {
+    pub fn value(self) -> HashMap<String, V> {
+        self.0.value()
+    }
+}
+
+impl<V> Deref for ItfObjectMap<V> {
+    type Target = HashMap<String, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<V> DerefMut for ItfObjectMap<V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'de, V> Deserialize<'de> for ItfObjectMap<V>
+where
+    V: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        const TAGS: &[&str] = &["#set", "#tup", "#bigint", "#unserializable"];
+
+        let object = serde_json::Map::deserialize(deserializer)?;
+
+        if object.contains_key("#map") {
+            return ItfMap::deserialize(ValueDeserializer::new(&serde_json::Value::Object(object)))
+                .map(Self);
+        }
+
+        if let Some(tag) = TAGS.iter().find(|tag| object.contains_key(**tag)) {
+            return Err(D::Error::custom(format!(
+                "expected a `#map` or a bare object but found a `{tag}`"
+            )));
+        }
+
+        object
+            .into_iter()
+            .map(
+                |(k, v)| match V::deserialize(ValueDeserializer::<D::Error>::new(&v)) {
+                    Ok(v) => Ok((k, v)),
+                    Err(e) => Err(located(k.as_str(), e)),
+                },
+            )
+            .collect::<Result<_, _>>()
+            .map(|map| Self(Itf(map)))
+    }
+}
+
+impl<V> Serialize for ItfObjectMap<V>
+where
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+struct Tup {
+    #[serde(rename = "#tup")]
+    elements: Vec<serde_json::Value>,
+}
+
+/// The empty tuple `<<>>`, decoded from a `#tup` without elements.
+impl<'de> Deserialize<'de> for Itf<()> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let elements = Tup::deserialize(deserializer).map(|t| t.elements)?;
+
+        if !elements.is_empty() {
+            return Err(raise(ItfError::TupleArity {
+                expected: 0,
+                found: elements.len(),
+            }));
+        }
+
+        Ok(Itf(()))
+    }
+}
+
+impl Serialize for Itf<()> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Tup", 1)?;
+        s.serialize_field("#tup", &[(); 0])?;
+        s.end()
+    }
+}
+
+macro_rules! deserialize_itf_tuple {
+    ($len:literal, $($n:literal $ty:ident)+) => {
+        impl<'de, $($ty ,)+> Deserialize<'de> for Itf<($($ty ,)+)>
+        where
+            $($ty: DeserializeOwned,)+
+        {
+            #[allow(non_snake_case)]
+            fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+            where
+                De: serde::Deserializer<'de>,
+            {
+                let elements = Tup::deserialize(deserializer).map(|t| t.elements)?;
+
+                if elements.len() != $len {
+                    return Err(raise(ItfError::TupleArity {
+                        expected: $len,
+                        found: elements.len(),
+                    }));
+                }
+
+                $(
+                    let $ty = ElementSeed::<$ty>::new()
+                        .deserialize(ValueDeserializer::<De::Error>::new(&elements[$n]))
+                        .map_err(|e| located(("#tup", $n), e))?;
+                )+
+
+                Ok(Itf(($($ty,)+)))
+            }
+        }
+    };
+}
+
+// Like serde itself, we support tuples with up to 16 elements.
+deserialize_itf_tuple!(2,  0 A 1 B);
+deserialize_itf_tuple!(3,  0 A 1 B 2 C);
+deserialize_itf_tuple!(4,  0 A 1 B 2 C 3 D);
+deserialize_itf_tuple!(5,  0 A 1 B 2 C 3 D 4 E);
+deserialize_itf_tuple!(6,  0 A 1 B 2 C 3 D 4 E 5 F);
+deserialize_itf_tuple!(7,  0 A 1 B 2 C 3 D 4 E 5 F 6 G);
+deserialize_itf_tuple!(8,  0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H);
+deserialize_itf_tuple!(9,  0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I);
+deserialize_itf_tuple!(10, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J);
+deserialize_itf_tuple!(11, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K);
+deserialize_itf_tuple!(12, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L);
+deserialize_itf_tuple!(13, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L 12 M);
+deserialize_itf_tuple!(14, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L 12 M 13 N);
+deserialize_itf_tuple!(15, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L 12 M 13 N 14 O);
+deserialize_itf_tuple!(16, 0 A 1 B 2 C 3 D 4 E 5 F 6 G 7 H 8 I 9 J 10 K 11 L 12 M 13 N 14 O 15 P);
+
+macro_rules! serialize_itf_tuple {
+    ($($ty:ident)+) => {
+        impl<$($ty ,)+> Serialize for Itf<($($ty ,)+)>
+        where
+            $($ty: Serialize,)+
+        {
+            #[allow(non_snake_case)]
+            fn serialize<Se>(&self, serializer: Se) -> Result<Se::Ok, Se::Error>
+            where
+                Se: serde::Serializer,
+            {
+                let ($($ty,)+) = &self.0;
+
+                let mut s = serializer.serialize_struct("Tup", 1)?;
+                s.serialize_field("#tup", &($(Element($ty),)+))?;
+                s.end()
+            }
+        }
+    };
+}
+
+serialize_itf_tuple!(A B);
+serialize_itf_tuple!(A B C);
+serialize_itf_tuple!(A B C D);
+serialize_itf_tuple!(A B C D E);
+serialize_itf_tuple!(A B C D E F);
+serialize_itf_tuple!(A B C D E F G);
+serialize_itf_tuple!(A B C D E F G H);
+serialize_itf_tuple!(A B C D E F G H I);
+serialize_itf_tuple!(A B C D E F G H I J);
+serialize_itf_tuple!(A B C D E F G H I J K);
+serialize_itf_tuple!(A B C D E F G H I J K L);
+serialize_itf_tuple!(A B C D E F G H I J K L M);
+serialize_itf_tuple!(A B C D E F G H I J K L M N);
+serialize_itf_tuple!(A B C D E F G H I J K L M N O);
+serialize_itf_tuple!(A B C D E F G H I J K L M N O P);
+
+/// Compares a decoded tuple with a plain one, for tuples with up to 12 elements,
+/// which is as far as `std` implements `PartialEq` for tuples.
+macro_rules! partial_eq_itf_tuple {
+    ($($ty:ident)+) => {
+        impl<$($ty ,)+> PartialEq<($($ty ,)+)> for Itf<($($ty ,)+)>
+        where
+            $($ty: PartialEq,)+
+        {
+            fn eq(&self, other: &($($ty ,)+)) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+partial_eq_itf_tuple!(A B);
+partial_eq_itf_tuple!(A B C);
+partial_eq_itf_tuple!(A B C D);
+partial_eq_itf_tuple!(A B C D E);
+partial_eq_itf_tuple!(A B C D E F);
+partial_eq_itf_tuple!(A B C D E F G);
+partial_eq_itf_tuple!(A B C D E F G H);
+partial_eq_itf_tuple!(A B C D E F G H I);
+partial_eq_itf_tuple!(A B C D E F G H I J);
+partial_eq_itf_tuple!(A B C D E F G H I J K);
+partial_eq_itf_tuple!(A B C D E F G H I J K L);
+
+/// A homogeneous tuple, decoded from a `#tup` with exactly `N` elements.
+impl<'de, T, const N: usize> Deserialize<'de> for Itf<[T; N]>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let elements = Tup::deserialize(deserializer).map(|t| t.elements)?;
+
+        if elements.len() != N {
+            return Err(raise(ItfError::TupleArity {
+                expected: N,
+                found: elements.len(),
+            }));
+        }
+
+        let elements = elements
+            .into_iter()
+            .enumerate()
+            .map(|(i, element)| {
+                ElementSeed::<T>::new()
+                    .deserialize(ValueDeserializer::<D::Error>::new(&element))
+                    .map_err(|e| located(("#tup", i), e))
+            })
+            .collect::<Result<Vec<T>, D::Error>>()?;
+
+        match elements.try_into() {
+            Ok(array) => Ok(Itf(array)),
+            Err(_) => unreachable!("the arity was checked above"),
+        }
+    }
+}
+
+impl<T, const N: usize> Serialize for Itf<[T; N]>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Tup", 1)?;
+        s.serialize_field("#tup", &Elements(self.0.iter()))?;
+        s.end()
+    }
+}
+
+impl<T> From<T> for ItfBigInt
+where
+    BigInt: From<T>,
+{
+    fn from(t: T) -> Self {
+        Itf(BigInt::from(t))
+    }
+}
+
+/// Error returned when converting an [`ItfBigInt`] to a fixed-width integer type.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TryFromBigIntError {
+    #[error("cannot convert negative value {value} to unsigned type `{target}`")]
+    Negative { value: BigInt, target: &'static str },
+
+    #[error("value {value} is out of range for type `{target}`")]
+    OutOfRange { value: BigInt, target: &'static str },
+}
+
+macro_rules! try_from_itf_bigint {
+    ($($ty:ty)+) => {
+        $(
+            impl TryFrom<ItfBigInt> for $ty {
+                type Error = TryFromBigIntError;
+
+                fn try_from(value: ItfBigInt) -> Result<Self, Self::Error> {
+                    <$ty>::try_from(&value.0).map_err(|_| {
+                        let target = stringify!($ty);
+
+                        if <$ty>::MIN == 0 && value.0.sign() == num_bigint::Sign::Minus {
+                            TryFromBigIntError::Negative { value: value.0, target }
+                        } else {
+                            TryFromBigIntError::OutOfRange { value: value.0, target }
+                        }
+                    })
+                }
+            }
+        )+
+    };
+}
+
+try_from_itf_bigint!(i64 u64 i128 u128 usize);
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
-    use serde_json::json;
+    use serde_json::json;
+
+    #[test]
+    fn deserialize_set() {
+        let json = json!({
+            "#set": [1, 2, 3, 4]
+        });
+
+        let set: ItfSet<ItfInt> = serde_json::from_value(json).unwrap();
+        let elems = [1_i64, 2, 3, 4].into_iter().collect::<HashSet<_>>();
+
+        assert_eq!(set.0, elems);
+    }
+
+    #[test]
+    fn eq_plain_value() {
+        let n: ItfBigInt = serde_json::from_value(json!({ "#bigint": "5" })).unwrap();
+        assert_eq!(n, BigInt::from(5));
+        assert_ne!(n, BigInt::from(6));
+        assert_eq!(n, ItfBigInt::from(5));
+
+        let s: ItfSet<i64> = serde_json::from_value(json!({ "#set": [1, 2] })).unwrap();
+        assert_eq!(s, HashSet::from([1, 2]));
+
+        let s: ItfBTreeSet<i64> = serde_json::from_value(json!({ "#set": [2, 1] })).unwrap();
+        assert_eq!(s, BTreeSet::from([1, 2]));
+
+        let m: ItfMap<String, i64> = serde_json::from_value(json!({ "#map": [["a", 1]] })).unwrap();
+        assert_eq!(m, HashMap::from([("a".to_string(), 1)]));
+
+        let m: ItfBTreeMap<i64, bool> =
+            serde_json::from_value(json!({ "#map": [[1, true]] })).unwrap();
+        assert_eq!(m, BTreeMap::from([(1, true)]));
+
+        let l: ItfList<i64> = serde_json::from_value(json!([1, 2])).unwrap();
+        assert_eq!(l, vec![1, 2]);
+        assert_ne!(l, vec![2, 1]);
+
+        let q: Itf<VecDeque<i64>> = serde_json::from_value(json!([1, 2])).unwrap();
+        assert_eq!(q, VecDeque::from([1, 2]));
+
+        let t: ItfTuple<(i64, String)> =
+            serde_json::from_value(json!({ "#tup": [1, "a"] })).unwrap();
+        assert_eq!(t, (1, "a".to_string()));
+    }
+
+    #[test]
+    fn set_operators() {
+        let s: ItfSet<i64> = [1, 2, 3].into_iter().collect();
+        let t: ItfSet<i64> = [2, 3, 4].into_iter().collect();
+        let u: ItfSet<i64> = [2, 3].into_iter().collect();
+
+        assert!(s.contains(&1));
+        assert!(!t.contains(&1));
+
+        assert!(u.is_subset(&s));
+        assert!(!s.is_subset(&t));
+        assert!(s.is_superset(&u));
+
+        assert_eq!(s.union(&t), HashSet::from([1, 2, 3, 4]));
+        assert_eq!(s.intersection(&t), u);
+        assert_eq!(s.difference(&t), HashSet::from([1]));
+    }
+
+    #[test]
+    fn sequence_operators() {
+        let s: ItfList<i64> = [10, 20, 30].into_iter().collect();
+
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.at(1).unwrap(), &10);
+        assert_eq!(s.at(3).unwrap(), &30);
+        assert_eq!(s.head().unwrap(), &10);
+        assert_eq!(s.tail().unwrap(), vec![20, 30]);
+
+        assert_eq!(s.at(0), Err(IndexOutOfDomain { index: 0, len: 3 }));
+        assert_eq!(
+            s.at(4).unwrap_err().to_string(),
+            "index 4 is not in the domain `1..3` of the sequence"
+        );
+
+        let empty: ItfList<i64> = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        assert!(empty.head().is_err());
+        assert!(empty.tail().is_err());
+    }
+
+    #[test]
+    fn deserialize_set_collapses_equal_elements() {
+        #[derive(Debug, PartialEq, Eq, Hash, Deserialize)]
+        struct Account {
+            owner: ItfString,
+            #[serde(skip)]
+            _id: (),
+        }
+
+        let json = json!({
+            "#set": [{ "owner": "alice", "id": 1 }, { "owner": "alice", "id": 2 }]
+        });
+
+        let set: ItfSet<Account> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(set.len(), 1);
+
+        let set: ItfSetVec<Account> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(set.len(), 2);
+
+        let err = crate::from_value::<ItfStrictSet<Account>>(&json).unwrap_err();
+        assert_eq!(err.path(), Some("#set[1]"));
+        assert!(matches!(err.inner(), ItfError::DuplicateElement));
+    }
+
+    #[test]
+    fn strict_set() {
+        let json = json!({ "#set": [1, 2, 3] });
+
+        let set: ItfStrictSet<i64> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(set.len(), 3);
+
+        let mut value = serde_json::to_value(&set).unwrap();
+        value["#set"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by_key(|v| v.as_i64());
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn set_of_enum() {
+        #[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        #[serde(rename_all = "UPPERCASE")]
+        enum Phase {
+            Init,
+            Working,
+            Done,
+        }
+
+        let json = json!({ "#set": ["INIT", "DONE"] });
+        let phases: ItfSet<Phase> = serde_json::from_value(json).unwrap();
+        assert_eq!(phases, HashSet::from([Phase::Init, Phase::Done]));
+
+        let json = json!({ "#map": [["p1", "WORKING"]] });
+        let procs: ItfMap<String, Phase> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(procs["p1"], Phase::Working);
+        assert_eq!(serde_json::to_value(&procs).unwrap(), json);
+
+        let err =
+            crate::from_value::<ItfSet<Phase>>(&json!({ "#set": ["INIT", "idle"] })).unwrap_err();
+        assert_eq!(err.path(), Some("#set[1]"));
+    }
+
+    #[test]
+    fn set_by_key() {
+        #[derive(Clone, Debug, Deserialize, Serialize)]
+        struct Account {
+            id: i64,
+            balance: i64,
+        }
+
+        struct ById;
+
+        impl SetKey<Account> for ById {
+            type Key = i64;
+
+            fn key(account: &Account) -> i64 {
+                account.id
+            }
+        }
+
+        let json = json!({
+            "#set": [
+                { "id": 2, "balance": 20 },
+                { "id": 1, "balance": 10 },
+                { "id": 2, "balance": 0 },
+            ]
+        });
+
+        let set: ItfSetBy<Account, ById> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set[&2].balance, 20);
+        assert!(set.contains(&Account { id: 1, balance: 0 }));
+
+        let set: ItfBTreeSetBy<Account, ById> = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&set).unwrap(),
+            json!({ "#set": [{ "id": 1, "balance": 10 }, { "id": 2, "balance": 20 }] })
+        );
+
+        let other = [Account { id: 2, balance: 5 }, Account { id: 1, balance: 5 }]
+            .into_iter()
+            .collect::<ItfBTreeSetBy<_, ById>>();
+        assert_eq!(set, other);
+    }
 
     #[test]
-    fn deserialize_set() {
+    fn object_map() {
+        let tagged: ItfObjectMap<ItfBigInt> =
+            serde_json::from_value(json!({ "#map": [["a", 1], ["b", { "#bigint": "2" }]] }))
+                .unwrap();
+        let bare: ItfObjectMap<ItfBigInt> =
+            serde_json::from_value(json!({ "a": 1, "b": { "#bigint": "2" } })).unwrap();
+
+        assert_eq!(tagged, bare);
+        assert_eq!(bare["b"], ItfBigInt::from(2));
+
+        let value = serde_json::to_value(ItfObjectMap::<i64>::default()).unwrap();
+        assert_eq!(value, json!({ "#map": [] }));
+
+        let err = crate::from_value::<ItfObjectMap<i64>>(&json!({ "#set": [1] })).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a `#map` or a bare object but found a `#set`"
+        );
+
+        let err = crate::from_value::<ItfObjectMap<i64>>(&json!({ "a": "one" })).unwrap_err();
+        assert_eq!(err.path(), Some("a"));
+    }
+
+    #[test]
+    fn serialize_set() {
         let json = json!({
             "#set": [1, 2, 3, 4]
         });
 
-        let set: ItfSet<ItfInt> = serde_json::from_value(json).unwrap();
-        let elems = [1_i64, 2, 3, 4].into_iter().collect::<HashSet<_>>();
+        let set: ItfSet<ItfInt> = serde_json::from_value(json.clone()).unwrap();
+        let mut value = serde_json::to_value(set).unwrap();
+        value["#set"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by_key(|v| v.as_i64());
+
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn serialize_set_of_bigints() {
+        let json = json!({
+            "#set": [
+                1,
+                { "#bigint": "1234567891011121314151617181920" },
+            ]
+        });
+
+        let set: ItfBTreeSet<ItfBigInt> = serde_json::from_value(json.clone()).unwrap();
+        let value = serde_json::to_value(set).unwrap();
+
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn deserialize_plain_bigint_elements() {
+        let big = "1234567891011121314151617181920".parse::<BigInt>().unwrap();
+
+        let json = json!({ "#set": [1, { "#bigint": big.to_string() }] });
+        let set: ItfSet<BigInt> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(set, HashSet::from([BigInt::from(1), big.clone()]));
+
+        let set: ItfSet<ItfBigInt> = serde_json::from_value(json).unwrap();
+        assert!(set.contains(&Itf(big.clone())));
+
+        let json = json!({ "#map": [[{ "#bigint": big.to_string() }, 2]] });
+        let map: ItfBTreeMap<BigInt, BigInt> = serde_json::from_value(json).unwrap();
+        assert_eq!(map[&big], BigInt::from(2));
+
+        let json = json!({ "#tup": [{ "#bigint": big.to_string() }, "a"] });
+        let tup: ItfTuple<(BigInt, String)> = serde_json::from_value(json).unwrap();
+        assert_eq!(tup.0 .0, big);
+
+        let err = crate::from_value::<ItfSet<BigInt>>(&json!({ "#set": [1, "a"] })).unwrap_err();
+        assert_eq!(err.path(), Some("#set[1]"));
+    }
+
+    #[test]
+    fn plain_bigint_elements_are_itf_integers() {
+        let big = "1234567891011121314151617181920".parse::<BigInt>().unwrap();
+
+        let set = ItfBTreeSet::from_iter([BigInt::from(-5), big.clone()]);
+        let json = json!({ "#set": [-5, { "#bigint": big.to_string() }] });
+        assert_eq!(serde_json::to_value(&set).unwrap(), json);
+
+        let map = ItfBTreeMap::from_iter([(BigInt::from(1), BigInt::from(2))]);
+        assert_eq!(
+            serde_json::to_value(&map).unwrap(),
+            json!({ "#map": [[1, 2]] })
+        );
+
+        let tup: ItfTuple<_> = Itf((BigInt::from(1), "a".to_string()));
+        assert_eq!(
+            serde_json::to_value(&tup).unwrap(),
+            json!({ "#tup": [1, "a"] })
+        );
+
+        // The serde encoding of a `BigInt`, ie. its sign and digits, is not an ITF integer.
+        let json = json!({ "#set": [[1, [5]]] });
+        assert!(serde_json::from_value::<ItfSet<BigInt>>(json).is_err());
+
+        // Nor is an ITF integer a pair which merely has the same shape.
+        let json = json!({ "#set": [5] });
+        assert!(serde_json::from_value::<ItfSet<(i64, Vec<u32>)>>(json).is_err());
+    }
+
+    #[test]
+    fn deserialize_array() {
+        let json = json!({ "#tup": [1, { "#bigint": "1234567891011121314151617181920" }, -3] });
+
+        let array: Itf<[ItfBigInt; 3]> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(array[0], ItfBigInt::from(1));
+        assert_eq!(
+            array[1],
+            Itf("1234567891011121314151617181920".parse::<BigInt>().unwrap())
+        );
+        assert_eq!(array[2], ItfBigInt::from(-3));
+        assert_eq!(serde_json::to_value(array).unwrap(), json);
+
+        let err = crate::from_value::<Itf<[ItfBigInt; 2]>>(&json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected tuple with 2 elements but found 3"
+        );
+
+        let err = crate::from_value::<Itf<[i64; 2]>>(&json!({ "#tup": [1, "a"] })).unwrap_err();
+        assert_eq!(err.path(), Some("#tup[1]"));
+    }
+
+    #[test]
+    #[cfg(feature = "ordered-float")]
+    fn deserialize_real() {
+        let real = |json| crate::from_value::<ItfReal>(&json).map(|r| r.value().0);
+
+        assert_eq!(real(json!(1.5)).unwrap(), 1.5);
+        assert_eq!(real(json!(-2)).unwrap(), -2.0);
+        assert_eq!(real(json!("0.25")).unwrap(), 0.25);
+        assert_eq!(real(json!({ "#bigint": "1000" })).unwrap(), 1000.0);
+
+        let err = real(json!("NaN")).unwrap_err();
+        assert_eq!(err.to_string(), "real `NaN` is not finite");
+        assert!(real(json!("one")).is_err());
+        assert_eq!(
+            real(json!(" 0.25")).unwrap_err().to_string(),
+            "invalid real ` 0.25`"
+        );
+
+        // A `#bigint` is an integer, in any of the bases of `ItfBigInt`
+        assert_eq!(real(json!({ "#bigint": "-0x10" })).unwrap(), -16.0);
+        let err = real(json!({ "#bigint": "0.5" })).unwrap_err();
+        assert!(matches!(err, ItfError::BigIntParse(s) if s == "0.5"));
+
+        let json = json!({ "#map": [[0.5, "half"], ["0.25", "quarter"]] });
+        let map: ItfBTreeMap<ItfReal, String> = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            map.values().map(String::as_str).collect::<Vec<_>>(),
+            ["quarter", "half"]
+        );
+        assert_eq!(
+            serde_json::to_value(&map).unwrap(),
+            json!({ "#map": [[0.25, "quarter"], [0.5, "half"]] })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn deserialize_instant() {
+        let instant = |json| serde_json::from_value::<ItfInstant>(json);
+
+        let t = instant(json!({ "#bigint": "1728892931714000001" })).unwrap();
+        assert_eq!(t.to_rfc3339(), "2024-10-14T08:02:11.714000001+00:00");
+        assert_eq!(
+            serde_json::to_value(t).unwrap(),
+            json!(1728892931714000001_i64)
+        );
+
+        assert_eq!(instant(json!(0)).unwrap().timestamp(), 0);
+
+        let err = instant(json!(-1)).unwrap_err();
+        assert_eq!(err.to_string(), "timestamp `-1` is before the Unix epoch");
+
+        let err = instant(json!({ "#bigint": "9223372036854775808" })).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "timestamp `9223372036854775808` is out of range"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "im")]
+    fn deserialize_im_collections() {
+        let json = json!({ "#map": [["alice", { "#set": [1, 2] }], ["bob", { "#set": [] }]] });
+
+        let map: ItfImMap<String, ItfImSet<i64>> = serde_json::from_value(json.clone()).unwrap();
+        let snapshot = map.clone();
+
+        assert!(map["alice"].contains(&2));
+        assert!(snapshot["bob"].is_empty());
+
+        let mut value = serde_json::to_value(&map).unwrap();
+        let entries = value["#map"].as_array_mut().unwrap();
+        entries.sort_by_key(|e| e[0].as_str().map(str::to_string));
+        entries[0][1]["#set"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by_key(|v| v.as_i64());
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn deserialize_empty() {
+        let set: ItfSet<i64> = serde_json::from_value(json!({ "#set": [] })).unwrap();
+        assert!(set.is_empty());
+
+        let set: ItfBTreeSet<ItfBigInt> = serde_json::from_value(json!({ "#set": [] })).unwrap();
+        assert!(set.is_empty());
+
+        let map: ItfMap<String, i64> = serde_json::from_value(json!({ "#map": [] })).unwrap();
+        assert!(map.is_empty());
+
+        let map: ItfBTreeMap<i64, ItfSet<String>> =
+            serde_json::from_value(json!({ "#map": [] })).unwrap();
+        assert!(map.is_empty());
+
+        let unit: Itf<()> = serde_json::from_value(json!({ "#tup": [] })).unwrap();
+        assert_eq!(serde_json::to_value(unit).unwrap(), json!({ "#tup": [] }));
+
+        let err = crate::from_value::<Itf<()>>(&json!({ "#tup": [1] })).unwrap_err();
+        assert!(matches!(
+            err,
+            ItfError::TupleArity {
+                expected: 0,
+                found: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn deserialize_map() {
+        let json = json!({
+            "#map": [["hello", 1], ["world", 2]]
+        });
+
+        let set: ItfMap<ItfString, ItfInt> = serde_json::from_value(json).unwrap();
+        let elems = [("hello".to_string(), 1), ("world".to_string(), 2)]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(set.0, elems);
+    }
+
+    #[test]
+    fn deserialize_map_vec() {
+        let json = json!({
+            "#map": [["zoe", 1], ["alice", 2], ["mallory", 3], ["bob", 4]]
+        });
+
+        let map: ItfMapVec<String, i64> = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(
+            map.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            ["zoe", "alice", "mallory", "bob"]
+        );
+        assert_eq!(map.get("mallory"), Some(&3));
+        assert_eq!(map.get("eve"), None);
+        assert_eq!(serde_json::to_value(&map).unwrap(), json);
+
+        // Keys need not be `Hash` nor `Ord`
+        let json = json!({ "#map": [[0.5, "half"], [0.25, "quarter"]] });
+        let map: ItfMapVec<f64, String> = serde_json::from_value(json).unwrap();
+        assert_eq!(map.get(&0.25).map(String::as_str), Some("quarter"));
+    }
+
+    #[test]
+    fn deserialize_map_with_tuple_keys() {
+        let json = json!({
+            "#map": [
+                [{ "#tup": [1, "a"] }, true],
+                [{ "#tup": [2, "b"] }, false],
+            ]
+        });
+
+        let map: ItfMap<ItfTuple<(ItfInt, ItfString)>, ItfBool> =
+            serde_json::from_value(json).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&Itf((1, "a".to_string()))), Some(&true));
+        assert_eq!(map.get(&Itf((2, "b".to_string()))), Some(&false));
+        assert_eq!(map.get(&Itf((1, "b".to_string()))), None);
+    }
+
+    #[test]
+    fn deserialize_map_with_record_keys() {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+        struct Account {
+            owner: ItfString,
+            id: ItfInt,
+        }
+
+        let json = json!({
+            "#map": [
+                [{ "owner": "alice", "id": 1 }, { "#bigint": "10" }],
+                [{ "owner": "bob", "id": 1 }, 20],
+            ]
+        });
+
+        let alice = ItfRecord(
+            Account {
+                owner: "alice".to_string(),
+                id: 1,
+            },
+            PhantomData,
+        );
+
+        let map: ItfMap<ItfRecord<Account>, ItfBigInt> =
+            serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&alice), Some(&ItfBigInt::from(10)));
+
+        let map: ItfBTreeMap<ItfRecord<Account>, ItfBigInt> = serde_json::from_value(json).unwrap();
+        assert_eq!(map.keys().next(), Some(&alice));
+    }
+
+    #[test]
+    fn function() {
+        let json = json!({ "#map": [["alice", 10], ["bob", { "#bigint": "5" }]] });
+
+        let balances: ItfFunction<ItfString, ItfBigInt> =
+            serde_json::from_value(json.clone()).unwrap();
+
+        let zero = ItfBigInt::from(0);
+        assert_eq!(balances.apply("alice"), Some(&ItfBigInt::from(10)));
+        assert_eq!(balances.apply("carol"), None);
+        assert_eq!(balances.get_or("bob", &zero), &ItfBigInt::from(5));
+        assert_eq!(balances.get_or("carol", &zero), &zero);
+
+        let map: ItfMap<ItfString, ItfBigInt> = serde_json::from_value(json).unwrap();
+        assert_eq!(balances.value(), map.value());
+    }
+
+    #[test]
+    fn serialize_map() {
+        let json = json!({
+            "#map": [["hello", 1], ["world", { "#bigint": "1234567891011121314151617181920" }]]
+        });
+
+        let map: ItfMap<ItfString, ItfBigInt> = serde_json::from_value(json.clone()).unwrap();
+        let mut value = serde_json::to_value(map).unwrap();
+        value["#map"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by_key(|kv| kv[0].as_str().unwrap().to_string());
+
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn serialize_map_empty() {
+        let map: ItfMap<ItfString, ItfInt> = Itf(HashMap::new());
+        let value = serde_json::to_value(map).unwrap();
+
+        assert_eq!(value, json!({ "#map": [] }));
+    }
+
+    #[test]
+    fn serialize_btree_map() {
+        let json = json!({
+            "#map": [[1, true], [2, false], [3, true]]
+        });
+
+        let map: ItfBTreeMap<ItfInt, ItfBool> = serde_json::from_value(json.clone()).unwrap();
+        let value = serde_json::to_value(map).unwrap();
+
+        assert_eq!(value, json);
+    }
+
+    #[test]
+    fn deserialize_btree_set() {
+        let json = json!({
+            "#set": [3, 1, 4, 2]
+        });
+
+        let set: ItfBTreeSet<ItfInt> = serde_json::from_value(json).unwrap();
+        let elems = set.iter().copied().collect::<Vec<_>>();
+
+        assert_eq!(elems, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn deserialize_btree_map() {
+        let json = json!({
+            "#map": [["world", 2], ["hello", 1], ["foo", 3]]
+        });
+
+        let map: ItfBTreeMap<ItfString, ItfInt> = serde_json::from_value(json).unwrap();
+        let elems = map
+            .iter()
+            .map(|(k, v)| (k.as_str(), *v))
+            .collect::<Vec<_>>();
+
+        assert_eq!(elems, vec![("foo", 3), ("hello", 1), ("world", 2)]);
+        assert_eq!(format!("{map:?}"), r#"{"foo": 3, "hello": 1, "world": 2}"#);
+    }
+
+    #[test]
+    fn deserialize_list() {
+        let json = json!([1, { "#bigint": "1234567891011121314151617181920" }]);
+
+        let list: ItfList<ItfBigInt> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            list.value(),
+            vec![
+                Itf(BigInt::from(1)),
+                Itf("1234567891011121314151617181920".parse().unwrap())
+            ]
+        );
+    }
+
+    #[test]
+    fn deserialize_list_empty() {
+        let json = json!([]);
+
+        let list: ItfList<ItfBigInt> = serde_json::from_value(json).unwrap();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn deserialize_list_of_tuples() {
+        let json = json!([
+            { "#tup": [1, "hello"] },
+            { "#tup": [2, "world"] },
+        ]);
+
+        let list: ItfList<ItfTuple<(ItfInt, ItfString)>> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            list.value(),
+            vec![Itf((1, "hello".to_string())), Itf((2, "world".to_string()))]
+        );
+    }
+
+    #[test]
+    fn deserialize_list_of_records() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct Transfer {
+            amount: ItfBigInt,
+            fees: ItfMap<String, ItfBigInt>,
+            tags: ItfSet<String>,
+        }
+
+        let json = json!([
+            {
+                "amount": { "#bigint": "1234567891011121314151617181920" },
+                "fees": { "#map": [["alice", 1], ["bob", { "#bigint": "2" }]] },
+                "tags": { "#set": ["urgent"] },
+            },
+            {
+                "amount": 5,
+                "fees": { "#map": [] },
+                "tags": { "#set": [] },
+            },
+        ]);
+
+        let log: ItfList<Transfer> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.head().unwrap().fees["bob"], ItfBigInt::from(2));
+        assert!(log.at(2).unwrap().fees.is_empty());
+
+        let log: Vec<Transfer> = serde_json::from_value(json).unwrap();
+        assert_eq!(log[1].amount, ItfBigInt::from(5));
+        assert!(log[0].tags.contains("urgent"));
+
+        let empty: ItfList<Transfer> = serde_json::from_value(json!([])).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn deserialize_queue() {
+        let json = json!([{ "#bigint": "1" }, 2, 3]);
+
+        let mut queue: Itf<VecDeque<ItfBigInt>> = serde_json::from_value(json).unwrap();
+        assert_eq!(queue.pop_front(), Some(ItfBigInt::from(1)));
+        assert_eq!(queue.back(), Some(&ItfBigInt::from(3)));
+
+        let queue: Itf<VecDeque<i64>> = serde_json::from_value(json!([])).unwrap();
+        assert!(queue.is_empty());
+
+        let err = crate::from_value::<Itf<VecDeque<i64>>>(&json!([1, "a"])).unwrap_err();
+        assert_eq!(err.path(), Some("[1]"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid type: map, expected a sequence")]
+    fn deserialize_list_from_tuple() {
+        let json = json!({ "#tup": [1, 2] });
+
+        let _list: ItfList<ItfInt> = serde_json::from_value(json).unwrap();
+    }
+
+    #[test]
+    fn deserialize_option() {
+        #[derive(Debug, Deserialize)]
+        struct State {
+            #[serde(default)]
+            x: ItfOption<ItfBigInt>,
+        }
+
+        let x = |json| serde_json::from_value::<State>(json).unwrap().x.value();
+
+        assert_eq!(x(json!({ "x": 1 })), Some(ItfBigInt::from(1)));
+        assert_eq!(
+            x(json!({ "x": { "#bigint": "2" } })),
+            Some(ItfBigInt::from(2))
+        );
+        assert_eq!(x(json!({ "x": { "#unserializable": "None" } })), None);
+
+        // Quint's `Option` type
+        assert_eq!(
+            x(json!({ "x": { "tag": "Some", "value": { "#bigint": "3" } } })),
+            Some(ItfBigInt::from(3))
+        );
+        assert_eq!(
+            x(json!({ "x": { "tag": "None", "value": { "#tup": [] } } })),
+            None
+        );
+
+        // Field absent
+        assert_eq!(x(json!({})), None);
+    }
+
+    #[test]
+    fn deserialize_option_null() {
+        #[derive(Debug, Deserialize)]
+        struct State {
+            #[serde(default)]
+            x: ItfOption<ItfInt>,
+        }
+
+        // ITF has no `null`, so a field present but `null` is not treated as absent
+        let err = crate::from_value::<State>(&json!({ "x": null })).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type: null, expected an ITF value, not null"
+        );
+        assert!(serde_json::from_str::<State>(r#"{ "x": null }"#).is_err());
+        assert_eq!(
+            serde_json::from_value::<State>(json!({}))
+                .unwrap()
+                .x
+                .value(),
+            None
+        );
+
+        let err = serde_json::from_value::<ItfList<ItfOption<ItfInt>>>(json!([1, null]));
+        assert!(err.is_err());
+
+        let xs: ItfList<ItfOption<ItfInt>> =
+            serde_json::from_str(r##"[1, { "#unserializable": "None" }]"##).unwrap();
+        assert_eq!(xs.value(), vec![Itf(Some(1)), Itf(None)]);
+    }
+
+    #[test]
+    fn deserialize_option_without_default() {
+        #[derive(Debug, Deserialize)]
+        struct State {
+            #[allow(dead_code)]
+            x: ItfOption<ItfInt>,
+        }
 
-        assert_eq!(set.0, elems);
+        // Like any other field, it must be marked `#[serde(default)]` to be absent
+        let err = crate::from_value::<State>(&json!({})).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `x`");
     }
 
     #[test]
-    fn deserialize_map() {
-        let json = json!({
-            "#map": [["hello", 1], ["world", 2]]
-        });
-
-        let set: ItfMap<ItfString, ItfInt> = serde_json::from_value(json).unwrap();
-        let elems = [("hello".to_string(), 1), ("world".to_string(), 2)]
-            .into_iter()
-            .collect::<HashMap<_, _>>();
+    fn deserialize_option_record() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct Transfer {
+            amount: ItfInt,
+        }
 
-        assert_eq!(set.0, elems);
+        let json = json!({ "tag": "Some", "value": { "amount": 5 } });
+        let transfer: ItfOption<ItfRecord<Transfer>> = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            transfer.value(),
+            Some(ItfRecord(Transfer { amount: 5 }, PhantomData))
+        );
     }
 
     #[test]
@@ -230,6 +3045,126 @@ mod tests {
         assert_eq!(bigint.0, "1234567891011121314151617181920".parse().unwrap());
     }
 
+    #[test]
+    fn deserialize_bigint_str() {
+        let json = json!("1234567891011121314151617181920");
+
+        let bigint: ItfBigInt = serde_json::from_value(json).unwrap();
+        assert_eq!(bigint.0, "1234567891011121314151617181920".parse().unwrap());
+    }
+
+    #[test]
+    fn deserialize_bigint_str_negative() {
+        let json = json!("-999999999999999999999999999999");
+
+        let bigint: ItfBigInt = serde_json::from_value(json).unwrap();
+        assert_eq!(bigint.0, "-999999999999999999999999999999".parse().unwrap());
+    }
+
+    #[test]
+    fn deserialize_bigint_str_whitespace() {
+        for s in [" 123", "123 ", "12 3", "", "abc", "-"] {
+            let result = serde_json::from_value::<ItfBigInt>(json!(s));
+            assert!(result.is_err(), "{s:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn deserialize_bigint_str_sign_and_separators() {
+        for s in ["1_000", "+5", "--5", "-+5", "+-5"] {
+            let result = serde_json::from_value::<ItfBigInt>(json!(s));
+            assert!(result.is_err(), "{s:?} should be rejected");
+
+            let result = serde_json::from_value::<ItfBigInt>(json!({ "#bigint": s }));
+            assert!(result.is_err(), "{s:?} should be rejected in a `#bigint`");
+        }
+    }
+
+    #[test]
+    fn deserialize_bigint_radix() {
+        let n = |s: &str| crate::from_value::<ItfBigInt>(&json!({ "#bigint": s }));
+
+        assert_eq!(n("0x1f4").unwrap(), BigInt::from(500));
+        assert_eq!(n("0X1F4").unwrap(), BigInt::from(500));
+        assert_eq!(n("-0x1f4").unwrap(), BigInt::from(-500));
+        assert_eq!(n("0o17").unwrap(), BigInt::from(15));
+        assert_eq!(n("0b101").unwrap(), BigInt::from(5));
+        assert_eq!(n("-0b101").unwrap(), BigInt::from(-5));
+        assert_eq!(
+            n("0xffffffffffffffffffffffffffffffff").unwrap(),
+            BigInt::from(u128::MAX)
+        );
+        assert_eq!(n("0101").unwrap(), BigInt::from(101));
+
+        for s in ["0b102", "0xg", "0x", "0x-1", "0x_1", "0x 1"] {
+            let err = n(s).unwrap_err();
+            assert!(
+                matches!(&err, ItfError::BigIntParse(value) if value == s),
+                "{s:?} should be rejected, got {err:?}"
+            );
+        }
+
+        let narrow = serde_json::from_value::<ItfNarrowInt>(json!({ "#bigint": "-0x10" }));
+        assert_eq!(narrow.unwrap().value(), -16);
+    }
+
+    #[test]
+    fn deserialize_narrow_int() {
+        let n = |json| crate::from_value::<ItfNarrowInt>(&json).map(Itf::value);
+
+        assert_eq!(n(json!(-1)).unwrap(), -1);
+        assert_eq!(n(json!(u64::MAX)).unwrap(), i128::from(u64::MAX));
+        assert_eq!(
+            n(json!({ "#bigint": i128::MIN.to_string() })).unwrap(),
+            i128::MIN
+        );
+
+        let overflow = (BigInt::from(i128::MAX) + 1_u64).to_string();
+        let err = n(json!({ "#bigint": overflow })).unwrap_err();
+        assert!(matches!(err, ItfError::IntOverflow(value) if value == overflow));
+
+        let err = n(json!({ "#bigint": "12x" })).unwrap_err();
+        assert!(matches!(err, ItfError::BigIntParse(_)));
+    }
+
+    #[test]
+    fn serialize_narrow_int() {
+        let json = json!([1, { "#bigint": i128::MAX.to_string() }]);
+        let ns: ItfList<ItfNarrowInt> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(ns.value()).unwrap(), json);
+    }
+
+    #[test]
+    fn serialize_bigint_bounds() {
+        let max = BigInt::from(i64::MAX);
+        let min = BigInt::from(i64::MIN);
+
+        let cases = [
+            (max.clone(), json!(i64::MAX)),
+            (min.clone(), json!(i64::MIN)),
+            (&max - 1, json!(i64::MAX - 1)),
+            (&min + 1, json!(i64::MIN + 1)),
+            (&max + 1, json!({ "#bigint": "9223372036854775808" })),
+            (&min - 1, json!({ "#bigint": "-9223372036854775809" })),
+        ];
+
+        for (n, expected) in cases {
+            let value = serde_json::to_value(Itf(n)).unwrap();
+            assert_eq!(value, expected);
+        }
+    }
+
+    #[test]
+    fn serialize_bigint_roundtrip() {
+        for json in [
+            json!(1024),
+            json!({ "#bigint": "-1234567891011121314151617181920" }),
+        ] {
+            let bigint: ItfBigInt = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(bigint).unwrap(), json);
+        }
+    }
+
     #[test]
     #[should_panic(expected = "expected tuple with 3 elements but found 2")]
     fn deserialize_tuple_wrong_cardinality() {
@@ -414,6 +3349,393 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_unserializable() {
+        let json = json!({
+            "#unserializable": "Nat"
+        });
+
+        let unser: ItfUnserializable = serde_json::from_value(json).unwrap();
+        assert_eq!(unser.as_str(), "Nat");
+    }
+
+    #[test]
+    fn deserialize_state_with_unserializable() {
+        #[derive(Debug, Deserialize)]
+        struct State {
+            counter: ItfBigInt,
+            domain: ItfUnserializable,
+            names: ItfSet<ItfString>,
+        }
+
+        let json = json!({
+            "counter": { "#bigint": "42" },
+            "domain": { "#unserializable": "[x \\in Nat |-> x + 1]" },
+            "names": { "#set": ["alice", "bob"] },
+        });
+
+        let state: State = serde_json::from_value(json).unwrap();
+
+        assert_eq!(state.counter, Itf(BigInt::from(42)));
+        assert_eq!(state.domain.as_str(), "[x \\in Nat |-> x + 1]");
+        assert_eq!(state.names.len(), 2);
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Bank {
+        balances: ItfMap<ItfString, ItfBigInt>,
+        owner: ItfString,
+    }
+
+    #[test]
+    fn deserialize_record() {
+        let json = json!({
+            "balances": { "#map": [["atom", { "#bigint": "1234567891011121314151617181920" }]] },
+            "owner": "alice",
+        });
+
+        let bank: ItfRecord<Bank> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(bank.owner, "alice");
+        assert_eq!(
+            bank.balances.get("atom"),
+            Some(&Itf("1234567891011121314151617181920".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown field `#tup`")]
+    fn deserialize_record_unknown_field() {
+        let json = json!({
+            "#tup": [1, 2],
+            "balances": { "#map": [] },
+            "owner": "alice",
+        });
+
+        let _bank: ItfRecord<Bank> = serde_json::from_value(json).unwrap();
+    }
+
+    #[test]
+    fn deserialize_record_allow_unknown_fields() {
+        let json = json!({
+            "#tup": [1, 2],
+            "balances": { "#map": [] },
+            "owner": "alice",
+            "ledger": { "#unknown": [] },
+        });
+
+        let bank: ItfRecord<Bank, AllowUnknownFields> = serde_json::from_value(json).unwrap();
+        assert_eq!(bank.owner, "alice");
+
+        // Structs which deny unknown fields still do
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct StrictBank {
+            owner: ItfString,
+        }
+
+        let bank: ItfRecord<StrictBank, AllowUnknownFields> =
+            serde_json::from_value(json!({ "owner": "alice" })).unwrap();
+        assert_eq!(bank.owner, "alice");
+
+        let json = json!({ "#set": [1, 2], "owner": "alice" });
+        let err =
+            crate::from_value::<ItfRecord<StrictBank, AllowUnknownFields>>(&json).unwrap_err();
+        assert!(err.to_string().contains("unknown field `#set`"));
+    }
+
+    #[test]
+    fn deserialize_record_catch_all_field() {
+        #[derive(Debug, Deserialize)]
+        struct LaxBank {
+            owner: ItfString,
+            #[serde(flatten)]
+            other: HashMap<String, serde_json::Value>,
+        }
+
+        let json = json!({
+            "#set": [1, 2],
+            "owner": "alice",
+        });
+
+        let bank: ItfRecord<LaxBank> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(bank.owner, "alice");
+        assert_eq!(bank.other["#set"], json!([1, 2]));
+    }
+
+    #[test]
+    fn deserialize_partial_state() {
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Owner {
+            owner: ItfString,
+        }
+
+        let json = json!({
+            "balances": { "#map": [["atom", { "#bigint": "not a number" }]] },
+            "ledger": { "#unknown": [] },
+            "owner": "alice",
+        });
+
+        let state: PartialState<Owner> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(state.owner, "alice");
+
+        // Plain structs also ignore unknown variables without decoding them
+        let state: Bank = serde_json::from_value(json!({
+            "balances": { "#map": [] },
+            "ledger": { "#unknown": [] },
+            "owner": "alice",
+        }))
+        .unwrap();
+        assert_eq!(state.owner, "alice");
+
+        assert!(serde_json::from_value::<Owner>(json).is_err());
+    }
+
+    #[test]
+    fn deserialize_tuple14() {
+        let json = json!({
+            "#tup": [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, { "#bigint": "13" }]
+        });
+
+        #[allow(clippy::type_complexity)]
+        let tuple: ItfTuple<(
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfInt,
+            ItfBigInt,
+        )> = serde_json::from_value(json).unwrap();
+
+        let (a, b, c, d, e, f, g, h, i, j, k, l, m, n) = tuple.value();
+        assert_eq!(
+            [a, b, c, d, e, f, g, h, i, j, k, l, m],
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(n, Itf(BigInt::from(13)));
+    }
+
+    #[test]
+    fn serialize_tuple() {
+        let json = json!({
+            "#tup": [
+                { "#bigint": "1234567891011121314151617181920" },
+                1234,
+                "Hello world",
+                true,
+            ]
+        });
+
+        let tuple: ItfTuple<(ItfBigInt, ItfInt, ItfString, ItfBool)> =
+            serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(tuple).unwrap(), json);
+    }
+
+    #[test]
+    fn serialize_nested_tuple() {
+        let tuple = Itf((Itf((1, "a".to_string())), Itf((Itf((true, 2)), 3))));
+
+        assert_eq!(
+            serde_json::to_value(tuple).unwrap(),
+            json!({
+                "#tup": [
+                    { "#tup": [1, "a"] },
+                    { "#tup": [{ "#tup": [true, 2] }, 3] },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_map_with_tuple_keys() {
+        let json = json!({
+            "#map": [
+                [{ "#tup": [1, "a"] }, { "#bigint": "1234567891011121314151617181920" }],
+                [{ "#tup": [2, "b"] }, 5],
+            ]
+        });
+
+        let map: ItfBTreeMap<ItfTuple<(ItfInt, ItfString)>, ItfBigInt> =
+            serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(map).unwrap(), json);
+    }
+
+    #[test]
+    fn to_itf_value() {
+        let mut trace = json!({
+            "states": [{ "balances": { "#map": [["alice", 1]] } }]
+        });
+
+        let mut balances: ItfMap<ItfString, ItfBigInt> =
+            serde_json::from_value(trace["states"][0]["balances"].clone()).unwrap();
+
+        balances.insert("alice".to_string(), Itf(BigInt::from(i64::MAX) + 1_u64));
+
+        let value = balances.to_itf_value().unwrap();
+        assert_eq!(value, serde_json::to_value(&balances).unwrap());
+
+        trace["states"][0]["balances"] = value;
+        assert_eq!(
+            trace,
+            json!({
+                "states": [{ "balances": { "#map": [["alice", { "#bigint": "9223372036854775808" }]] } }]
+            })
+        );
+    }
+
+    #[test]
+    fn map_and_as_ref() {
+        let set: ItfSet<ItfInt> = serde_json::from_value(json!({ "#set": [1, 2, 3] })).unwrap();
+
+        assert_eq!(set.as_ref().map(|s| s.len()).value(), 3);
+        assert_eq!(set.map(|s| s.into_iter().sum::<i64>()).value(), 6);
+    }
+
+    #[test]
+    fn into_iter_set() {
+        let set: ItfSet<ItfInt> = serde_json::from_value(json!({ "#set": [1, 2, 3] })).unwrap();
+
+        let mut borrowed = Vec::new();
+        for x in &set {
+            borrowed.push(*x);
+        }
+        borrowed.sort();
+        assert_eq!(borrowed, vec![1, 2, 3]);
+
+        let mut owned = Vec::new();
+        for x in set {
+            owned.push(x);
+        }
+        owned.sort();
+        assert_eq!(owned, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn into_iter_map() {
+        let json = json!({ "#map": [["hello", 1], ["world", 2]] });
+        let mut map: ItfMap<ItfString, ItfInt> = serde_json::from_value(json).unwrap();
+
+        for (_, v) in &mut map {
+            *v *= 10;
+        }
+
+        let mut entries = map.into_iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![("hello".to_string(), 10), ("world".to_string(), 20)]
+        );
+    }
+
+    #[test]
+    fn into_iter_btree() {
+        let set: ItfBTreeSet<ItfInt> =
+            serde_json::from_value(json!({ "#set": [3, 1, 2] })).unwrap();
+        assert_eq!(
+            (&set).into_iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let map: ItfBTreeMap<ItfInt, ItfBool> =
+            serde_json::from_value(json!({ "#map": [[2, false], [1, true]] })).unwrap();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            vec![(1, true), (2, false)]
+        );
+    }
+
+    #[test]
+    fn from_iter_set() {
+        let set: ItfBTreeSet<_> = (1..=3).map(ItfBigInt::from).collect();
+
+        assert_eq!(
+            serde_json::to_value(set).unwrap(),
+            json!({ "#set": [1, 2, 3] })
+        );
+
+        let set: ItfSet<ItfInt> = (0..10).collect();
+        assert_eq!(set.len(), 10);
+    }
+
+    #[test]
+    fn from_iter_map() {
+        let map: ItfBTreeMap<_, _> = [("a".to_string(), true), ("b".to_string(), false)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            serde_json::to_value(map).unwrap(),
+            json!({ "#map": [["a", true], ["b", false]] })
+        );
+
+        let map: ItfMap<ItfInt, ItfInt> = (0..10).map(|i| (i, i * i)).collect();
+        assert_eq!(map.get(&3), Some(&9));
+    }
+
+    #[test]
+    fn try_from_bigint() {
+        assert_eq!(u64::try_from(ItfBigInt::from(42)), Ok(42));
+        assert_eq!(usize::try_from(ItfBigInt::from(42)), Ok(42));
+        assert_eq!(i128::try_from(ItfBigInt::from(-42)), Ok(-42));
+        assert_eq!(u128::try_from(ItfBigInt::from(u128::MAX)), Ok(u128::MAX));
+        assert_eq!(i64::try_from(ItfBigInt::from(i64::MIN)), Ok(i64::MIN));
+    }
+
+    #[test]
+    fn try_from_bigint_overflow() {
+        let big = BigInt::from(u64::MAX) + 1_u64;
+
+        assert_eq!(
+            u64::try_from(Itf(big.clone())),
+            Err(TryFromBigIntError::OutOfRange {
+                value: big.clone(),
+                target: "u64"
+            })
+        );
+        assert_eq!(u128::try_from(Itf(big.clone())), Ok(u64::MAX as u128 + 1));
+
+        let err = i64::try_from(Itf(-big.clone())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "value -18446744073709551616 is out of range for type `i64`"
+        );
+    }
+
+    #[test]
+    fn try_from_bigint_negative_to_unsigned() {
+        let err = u64::try_from(ItfBigInt::from(-1)).unwrap_err();
+
+        assert_eq!(
+            err,
+            TryFromBigIntError::Negative {
+                value: BigInt::from(-1),
+                target: "u64"
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "cannot convert negative value -1 to unsigned type `u64`"
+        );
+
+        assert!(matches!(
+            usize::try_from(ItfBigInt::from(-1)),
+            Err(TryFromBigIntError::Negative { .. })
+        ));
+    }
+
     #[test]
     fn display() {
         let s = "1234567891011121314151617181920";
@@ -0,0 +1,265 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+use crate::{error::located, ItfError, State, TraceCheck, TraceMeta, ValidationIssue};
+
+/// A trace whose states are only decoded when accessed.
+///
+/// Unlike a [`TraceReader`](crate::TraceReader), it keeps all the states in memory,
+/// as JSON values, so that they can be accessed in any order, eg. to bisect
+/// the first state which violates an invariant.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(bound(deserialize = ""))]
+pub struct LazyTrace<S> {
+    #[serde(rename = "#meta")]
+    pub meta: TraceMeta,
+
+    #[serde(default)]
+    pub params: Vec<String>,
+
+    #[serde(default)]
+    pub vars: Vec<String>,
+
+    #[serde(default, rename = "loop")]
+    pub loop_index: Option<u64>,
+
+    pub(crate) states: Vec<Value>,
+
+    #[serde(skip)]
+    cache: Vec<Option<State<S>>>,
+}
+
+impl<S> LazyTrace<S>
+where
+    S: DeserializeOwned,
+{
+    /// Returns the number of states of the trace.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns whether the trace has no states.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Decodes the state at index `i`.
+    ///
+    /// Returns an error if the state fails to decode, or [`ItfError::MissingState`]
+    /// if there is no state at index `i`.
+    pub fn state(&self, i: usize) -> Result<State<S>, ItfError> {
+        let value = self.states.get(i).ok_or(ItfError::MissingState {
+            index: i,
+            len: self.len(),
+        })?;
+
+        crate::from_value(value).map_err(|e| located(("states", i), e))
+    }
+
+    /// Decodes the states of the trace one after the other, along with their index.
+    pub fn iter_states(&self) -> impl Iterator<Item = (usize, Result<State<S>, ItfError>)> + '_ {
+        (0..self.len()).map(|i| (i, self.state(i)))
+    }
+
+    /// Decodes the transitions of the trace, ie. the pairs of consecutive states,
+    /// decoding each state once.
+    ///
+    /// A state which fails to decode yields its error, in place of the
+    /// transitions to and from it.
+    pub fn iter_transitions(
+        &self,
+    ) -> impl Iterator<Item = Result<(State<S>, State<S>), ItfError>> + '_
+    where
+        S: Clone,
+    {
+        let mut previous = None;
+
+        (0..self.len()).filter_map(move |i| match self.state(i) {
+            Ok(state) => previous
+                .replace(state.clone())
+                .map(|previous| Ok((previous, state))),
+            Err(e) => {
+                previous = None;
+                Some(Err(e))
+            }
+        })
+    }
+
+    /// Decodes the values of the parameters declared in the `params` section.
+    ///
+    /// Parameters have the same value in every state of the trace, so they
+    /// are decoded from the first state, ignoring its other variables.
+    pub fn params<P>(&self) -> Result<P, ItfError>
+    where
+        P: DeserializeOwned,
+    {
+        crate::trace::decode_params(&self.params, self.states.first())
+    }
+
+    /// Decodes the state at index `i`, or returns it from the cache if it was
+    /// decoded by a previous call to this method.
+    pub fn state_cached(&mut self, i: usize) -> Result<&State<S>, ItfError> {
+        if self.cache.len() < self.states.len() {
+            self.cache.resize_with(self.states.len(), || None);
+        }
+
+        if self.cache.get(i).map_or(true, Option::is_none) {
+            let state = self.state(i)?;
+            self.cache[i] = Some(state);
+        }
+
+        Ok(self.cache[i].as_ref().expect("state was just cached"))
+    }
+
+    /// Drops the decoded states kept by [`LazyTrace::state_cached`].
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl<S> LazyTrace<S> {
+    /// Checks the consistency of the trace, independently of the values of its variables:
+    ///
+    /// - the `#meta.index` of each state, if any, is its position in the trace,
+    /// - each state only has variables declared in the `vars` or `params` section,
+    /// - each state has a value for every parameter declared in the `params` section.
+    ///
+    /// The variables of a state are those found in the trace, whatever the state
+    /// type models of them, and the states are not decoded.
+    ///
+    /// Returns all the issues found, in the order of the states.
+    ///
+    /// Use [`TraceCheck`] to skip some of the checks, eg. the one on indices.
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        TraceCheck::default().validate_lazy(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ItfBigInt, ItfSet};
+
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+    struct Counter {
+        count: ItfBigInt,
+    }
+
+    fn trace() -> LazyTrace<Counter> {
+        serde_json::from_value(serde_json::json!({
+            "#meta": {},
+            "vars": ["count"],
+            "states": [
+                { "#meta": { "index": 0 }, "count": 0 },
+                { "#meta": { "index": 1 }, "count": { "#bigint": "1" } },
+                { "#meta": { "index": 2 }, "count": "two" },
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn random_access() {
+        let trace = trace();
+
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.state(1).unwrap().value.count, ItfBigInt::from(1));
+        assert_eq!(trace.state(0).unwrap().meta.index, Some(0));
+    }
+
+    #[test]
+    fn decode_error() {
+        let trace = trace();
+
+        let err = trace.state(2).unwrap_err();
+        assert_eq!(err.path(), Some("states[2]"));
+
+        let err = trace.state(3).unwrap_err();
+        assert!(matches!(err, ItfError::MissingState { index: 3, len: 3 }));
+        assert_eq!(
+            err.to_string(),
+            "no state at index 3, the trace has 3 states"
+        );
+    }
+
+    #[test]
+    fn iter_transitions() {
+        let trace = trace();
+
+        let states = trace.iter_states().collect::<Vec<_>>();
+        assert_eq!(states.len(), 3);
+        assert!(states[1].1.is_ok());
+        assert!(states[2].1.is_err());
+
+        let transitions = trace.iter_transitions().collect::<Vec<_>>();
+        assert_eq!(transitions.len(), 2);
+
+        let (from, to) = transitions[0].as_ref().unwrap();
+        assert_eq!(from.value.count, ItfBigInt::from(0));
+        assert_eq!(to.value.count, ItfBigInt::from(1));
+
+        let err = transitions[1].as_ref().unwrap_err();
+        assert_eq!(err.path(), Some("states[2]"));
+    }
+
+    #[test]
+    fn params() {
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "UPPERCASE")]
+        struct Params {
+            n: ItfBigInt,
+            procs: ItfSet<String>,
+        }
+
+        let trace: LazyTrace<Counter> = serde_json::from_value(serde_json::json!({
+            "#meta": {},
+            "params": ["N", "PROCS"],
+            "vars": ["count"],
+            "states": [
+                {
+                    "N": { "#bigint": "3" },
+                    "PROCS": { "#set": ["p1", "p2"] },
+                    "count": 0,
+                },
+            ]
+        }))
+        .unwrap();
+
+        let params = trace.params::<Params>().unwrap();
+        assert_eq!(params.n, ItfBigInt::from(3));
+        assert!(params.procs.contains("p2"));
+
+        // Variables are not parameters
+        let err = trace.params::<Counter>().unwrap_err();
+        assert_eq!(err.path(), Some("states[0]"));
+    }
+
+    #[test]
+    fn cached() {
+        let mut trace = trace();
+
+        let first = trace.state_cached(1).unwrap() as *const _;
+        let second = trace.state_cached(1).unwrap() as *const _;
+        assert_eq!(first, second);
+
+        assert!(trace.state_cached(2).is_err());
+
+        trace.clear_cache();
+        assert_eq!(
+            trace.state_cached(1).unwrap().value.count,
+            ItfBigInt::from(1)
+        );
+    }
+
+    #[test]
+    fn bisect() {
+        let trace = trace();
+
+        // Find the first state which fails to decode
+        let states = (0..trace.len()).collect::<Vec<_>>();
+        let first_bad = states.partition_point(|&i| trace.state(i).is_ok());
+
+        assert_eq!(first_bad, 2);
+    }
+}
@@ -7,7 +7,7 @@
 //! ```rust
 //! use serde::Deserialize;
 //!
-//! use itf::{trace_from_str, ItfMap, ItfSet};
+//! use itf::{trace_from_str, ItfMap, ItfSet, Trace};
 //!
 //! #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
 //! enum Bank {
@@ -53,19 +53,23 @@
 //!
 //! **Output:**
 //!
-//! ```rust
+//! ```text
 //! trace = Trace {
 //!     meta: TraceMeta {
-//!         description: None,
+//!         format: None,
+//!         format_description: None,
 //!         source: Some(
 //!             "MC_MissionariesAndCannibalsTyped.tla",
 //!         ),
+//!         description: None,
 //!         var_types: {
 //!             "bank_of_boat": "Str",
 //!             "who_is_on_bank": "Str -> Set(PERSON)",
 //!         },
-//!         format: None,
-//!         format_description: None,
+//!         timestamp: None,
+//!         format_version: None,
+//!         tool: None,
+//!         rand_seed: None,
 //!         other: {},
 //!     },
 //!     params: [],
@@ -205,51 +209,125 @@
 
 mod util;
 
+#[cfg(feature = "derive")]
+pub use itf_derive::ItfState;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::util::derive::Vars;
+    pub use serde;
+}
+
+mod error;
+pub use error::*;
+
 mod meta;
 pub use meta::*;
 
 mod itf;
 pub use itf::*;
 
+mod value;
+pub use value::*;
+
+mod intern;
+pub use intern::*;
+
 mod trace;
-use serde::{de::DeserializeOwned, Deserialize};
 pub use trace::*;
 
-use serde_json::Result;
+mod lazy;
+pub use lazy::*;
+
+mod format;
+pub use format::*;
+
+mod reader;
+pub use reader::*;
+
+mod mbt;
+pub use mbt::*;
+
+mod diff;
+pub use diff::*;
 
-pub fn trace_from_str<'a, State>(s: &'a str) -> Result<Trace<State>>
+use serde::{de::DeserializeOwned, Deserialize};
+
+use util::serde::value::ValueDeserializer;
+
+/// Decodes an ITF value, eg. an `ItfSet<ItfBigInt>` or the state of a trace,
+/// from a JSON value.
+///
+/// Unlike `serde_json::from_value`, the errors keep their kind and the path
+/// to the value which failed to decode:
+///
+/// ```
+/// use itf::{ItfBigInt, ItfError, ItfSet};
+///
+/// let json = serde_json::json!({ "#set": [1, { "#bigint": "x" }] });
+/// let err = itf::from_value::<ItfSet<ItfBigInt>>(&json).unwrap_err();
+///
+/// assert_eq!(err.path(), Some("#set[1]"));
+/// assert!(matches!(err.inner(), ItfError::BigIntParse(_)));
+/// ```
+pub fn from_value<'a, T>(value: &'a serde_json::Value) -> Result<T, ItfError>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(ValueDeserializer::new(value))
+}
+
+/// Decodes an ITF value from a string of JSON, like [`from_value`].
+pub fn from_str<T>(s: &str) -> Result<T, ItfError>
+where
+    T: DeserializeOwned,
+{
+    from_value(&serde_json::from_str(s)?)
+}
+
+/// Decodes an ITF value from a slice of bytes of JSON, like [`from_value`].
+pub fn from_slice<T>(bytes: &[u8]) -> Result<T, ItfError>
 where
-    State: Deserialize<'a>,
+    T: DeserializeOwned,
 {
-    serde_json::from_str(s)
+    from_value(&serde_json::from_slice(bytes)?)
 }
 
-pub fn trace_from_slice<'a, State>(s: &'a [u8]) -> Result<Trace<State>>
+pub fn trace_from_str<State>(s: &str) -> Result<Trace<State>, ItfError>
 where
-    State: Deserialize<'a>,
+    State: DeserializeOwned,
 {
-    serde_json::from_slice(s)
+    s.parse()
 }
 
-pub fn trace_from_value<State>(v: serde_json::Value) -> Result<Trace<State>>
+pub fn trace_from_slice<State>(s: &[u8]) -> Result<Trace<State>, ItfError>
 where
     State: DeserializeOwned,
 {
-    serde_json::from_value(v)
+    Trace::from_slice(s)
 }
 
-pub fn trace_from_reader<State, R>(r: R) -> Result<Trace<State>>
+pub fn trace_from_value<State>(v: serde_json::Value) -> Result<Trace<State>, ItfError>
+where
+    State: DeserializeOwned,
+{
+    Trace::from_value(&v)
+}
+
+pub fn trace_from_reader<State, R>(r: R) -> Result<Trace<State>, ItfError>
 where
     State: DeserializeOwned,
     R: std::io::Read,
 {
-    serde_json::from_reader(r)
+    Trace::from_reader(r)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use serde::Deserialize;
+
     #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
     enum Bank {
         #[serde(rename = "N")]
@@ -0,0 +1,185 @@
+//! Support for model-based testing traces, as produced eg. by `quint run --mbt`.
+//!
+//! Such traces record, in each state, the action taken to reach that state
+//! in the `mbt::actionTaken` variable, and the nondeterministic choices made
+//! by that action in the `mbt::nondetPicks` variable.
+//!
+//! ## Routing actions
+//!
+//! Decode the trace as a `Trace<MbtState<S>>` and dispatch on the action name:
+//!
+//! ```rust
+//! use serde::Deserialize;
+//!
+//! use itf::{trace_from_value, ItfBigInt, MbtState, Trace};
+//!
+//! #[derive(Debug, Deserialize)]
+//! struct Bank {
+//!     balance: ItfBigInt,
+//! }
+//!
+//! let json = serde_json::json!({
+//!     "#meta": {},
+//!     "states": [
+//!         { "mbt::actionTaken": "init", "mbt::nondetPicks": {}, "balance": 0 },
+//!         {
+//!             "mbt::actionTaken": "deposit",
+//!             "mbt::nondetPicks": { "amount": { "tag": "Some", "value": 50 } },
+//!             "balance": 50
+//!         },
+//!     ]
+//! });
+//!
+//! let trace: Trace<MbtState<Bank>> = trace_from_value(json).unwrap();
+//!
+//! for state in &trace.states {
+//!     let state = &state.value;
+//!
+//!     match state.action() {
+//!         Some("init") | None => { /* initialize the system under test */ }
+//!         Some("deposit") => {
+//!             let amount: ItfBigInt = state.nondet_pick("amount").unwrap().unwrap();
+//!             assert_eq!(amount, state.balance);
+//!         }
+//!         Some(action) => panic!("unknown action: {action}"),
+//!     }
+//! }
+//! ```
+
+use std::ops::{Deref, DerefMut};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+/// The variables of a state in a model-based testing trace,
+/// along with the variables `S` of the model itself.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MbtState<S> {
+    #[serde(default, rename = "mbt::actionTaken")]
+    pub action_taken: String,
+
+    #[serde(default, rename = "mbt::nondetPicks")]
+    pub nondet_picks: serde_json::Map<String, serde_json::Value>,
+
+    #[serde(flatten)]
+    pub value: S,
+}
+
+impl<S> MbtState<S> {
+    /// The name of the action taken to reach this state.
+    ///
+    /// Returns `None` if no action was recorded, which is typically
+    /// the case in the initial state. Some producers will instead
+    /// record the `init` action there.
+    pub fn action(&self) -> Option<&str> {
+        if self.action_taken.is_empty() {
+            None
+        } else {
+            Some(&self.action_taken)
+        }
+    }
+
+    /// Decodes the value picked for the nondeterministic variable `name`.
+    ///
+    /// Returns `Ok(None)` if the variable is not present, or if no value
+    /// was picked for it, ie. it is encoded as the variant `None`.
+    pub fn nondet_pick<T>(&self, name: &str) -> Result<Option<T>, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let value = match self.nondet_picks.get(name) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let tag = value.get("tag").and_then(|tag| tag.as_str());
+
+        // Older producers emit the picked value directly instead of an option variant
+        match (tag, value.get("value")) {
+            (Some("None"), Some(_)) => Ok(None),
+            (Some("Some"), Some(value)) => T::deserialize(value).map(Some),
+            _ => T::deserialize(value).map(Some),
+        }
+    }
+}
+
+impl<S> Deref for MbtState<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<S> DerefMut for MbtState<S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::{ItfBigInt, ItfInt, ItfString, State};
+
+    #[derive(Debug, PartialEq, Eq, Deserialize)]
+    struct Bank {
+        balance: ItfBigInt,
+    }
+
+    #[test]
+    fn initial_state() {
+        let json = json!({
+            "#meta": { "index": 0 },
+            "mbt::actionTaken": "",
+            "mbt::nondetPicks": {},
+            "balance": 0,
+        });
+
+        let state: State<MbtState<Bank>> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(state.meta.index, Some(0));
+        assert_eq!(state.value.action(), None);
+        assert_eq!(state.value.nondet_pick::<ItfInt>("amount").unwrap(), None);
+        assert_eq!(state.value.balance, ItfBigInt::from(0));
+    }
+
+    #[test]
+    fn initial_state_without_mbt_vars() {
+        let json = json!({ "balance": 0 });
+
+        let state: MbtState<Bank> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(state.action(), None);
+        assert!(state.nondet_picks.is_empty());
+    }
+
+    #[test]
+    fn step_state() {
+        let json = json!({
+            "mbt::actionTaken": "transfer",
+            "mbt::nondetPicks": {
+                "amount": { "tag": "Some", "value": { "#bigint": "50" } },
+                "memo": { "tag": "None", "value": { "#tup": [] } },
+                "receiver": "bob",
+            },
+            "balance": 50,
+        });
+
+        let state: MbtState<Bank> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(state.action(), Some("transfer"));
+        assert_eq!(
+            state.nondet_pick::<ItfBigInt>("amount").unwrap(),
+            Some(ItfBigInt::from(50))
+        );
+        assert_eq!(state.nondet_pick::<ItfString>("memo").unwrap(), None);
+        assert_eq!(
+            state.nondet_pick::<ItfString>("receiver").unwrap(),
+            Some("bob".to_string())
+        );
+        assert!(state.nondet_pick::<ItfInt>("receiver").is_err());
+    }
+}
@@ -1,29 +1,143 @@
 use std::collections::HashMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TraceMeta {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
 
-    #[serde(rename = "format-description")]
+    #[serde(
+        default,
+        rename = "format-description",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub format_description: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
-    #[serde(default, rename = "varTypes")]
+    #[serde(
+        default,
+        rename = "varTypes",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
     pub var_types: HashMap<String, String>,
 
-    #[serde(default)]
-    pub timestamp: Option<u64>,
+    /// The creation time of the trace, as recorded by its producer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<Timestamp>,
+
+    /// The version of the ITF format the trace follows, if recorded.
+    #[serde(
+        default,
+        rename = "format-version",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub format_version: Option<String>,
+
+    /// The tool which produced the trace, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+
+    /// The seed of the random generator which produced the trace, if any.
+    /// Depending on their version, producers record it as a number or as a string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rand_seed: Option<RandSeed>,
 
     #[serde(flatten)]
-    pub other: HashMap<String, String>,
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TraceMeta {
+    /// Returns the version of the tool which produced the trace, if it is
+    /// recorded as a string, eg. by Quint.
+    ///
+    /// The `version` field is kept in [`TraceMeta::other`], as it is, since
+    /// some producers record it as a number.
+    pub fn version(&self) -> Option<&str> {
+        self.other
+            .get("version")
+            .and_then(serde_json::Value::as_str)
+    }
+}
+
+/// The creation time of a trace, as recorded by its producer, which is
+/// serialized back as it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Timestamp {
+    /// A number of milliseconds since the Unix epoch, as recorded by Quint
+    Number(u64),
+
+    /// A timestamp in a format chosen by the producer, eg. RFC 3339
+    String(String),
+}
+
+/// The seed of the random generator which produced a trace, which is
+/// serialized back as it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum RandSeed {
+    /// A seed recorded as a number
+    Number(u64),
+
+    /// A seed recorded as a string, in decimal or in hexadecimal prefixed with `0x`
+    String(String),
+}
+
+impl RandSeed {
+    /// Returns the value of the seed, or `None` if it is recorded as a string
+    /// which is not a valid seed, which decoding a trace rejects.
+    pub fn value(&self) -> Option<u64> {
+        match self {
+            RandSeed::Number(seed) => Some(*seed),
+            RandSeed::String(seed) => {
+                let (radix, digits) = match seed.strip_prefix("0x") {
+                    Some(digits) => (16, digits),
+                    None => (10, seed.as_str()),
+                };
+
+                // `from_str_radix` would accept a leading `+`
+                if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+                    return None;
+                }
+
+                u64::from_str_radix(digits, radix).ok()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RandSeed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Seed {
+            Number(u64),
+            String(String),
+        }
+
+        let seed = match Seed::deserialize(deserializer)? {
+            Seed::Number(seed) => RandSeed::Number(seed),
+            Seed::String(seed) => RandSeed::String(seed),
+        };
+
+        match (seed.value(), seed) {
+            (None, RandSeed::String(seed)) => {
+                Err(D::Error::custom(format!("invalid random seed `{seed}`")))
+            }
+            (_, seed) => Ok(seed),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -32,5 +146,5 @@ pub struct StateMeta {
     pub index: Option<u64>,
 
     #[serde(flatten)]
-    pub other: HashMap<String, String>,
+    pub other: serde_json::Map<String, serde_json::Value>,
 }
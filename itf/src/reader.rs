@@ -0,0 +1,559 @@
+use std::{
+    io::{self, BufReader, Bytes, Read},
+    marker::PhantomData,
+};
+
+use serde::de::{DeserializeOwned, Error as _, IgnoredAny};
+use serde_json::{Error, Result as JsonResult};
+
+use crate::{error::located, ItfError, State, TraceMeta};
+
+/// Reads the states of a trace one at a time, instead of loading the whole
+/// trace into memory like [`trace_from_reader`](crate::trace_from_reader).
+///
+/// The sections of the trace which precede `states` in the file, such as
+/// `#meta` and `vars`, are read by [`TraceReader::new`]. Those which follow
+/// it are only available once all the states have been read.
+///
+/// ```
+/// # use itf::{ItfBigInt, TraceReader};
+/// # #[derive(serde::Deserialize)]
+/// # struct Counter { count: ItfBigInt }
+/// let json = r##"{
+///     "#meta": {},
+///     "vars": ["count"],
+///     "states": [{ "count": 0 }, { "count": { "#bigint": "1" } }]
+/// }"##;
+///
+/// let reader = TraceReader::<Counter, _>::new(json.as_bytes())?;
+/// assert_eq!(reader.vars(), ["count"]);
+///
+/// for state in reader {
+///     let state = state?;
+///     println!("count = {}", *state.value.count);
+/// }
+/// # Ok::<(), itf::ItfError>(())
+/// ```
+pub struct TraceReader<S, R> {
+    input: Input<R>,
+    meta: TraceMeta,
+    params: Vec<String>,
+    vars: Vec<String>,
+    loop_index: Option<u64>,
+    index: usize,
+    done: bool,
+    marker: PhantomData<S>,
+}
+
+impl<S, R> TraceReader<S, R>
+where
+    S: DeserializeOwned,
+    R: Read,
+{
+    /// Reads the trace up to its first state.
+    ///
+    /// Returns an error if the input is not a JSON object with a `states` array.
+    pub fn new(reader: R) -> Result<Self, ItfError> {
+        let mut this = Self {
+            input: Input::new(reader),
+            meta: TraceMeta::default(),
+            params: Vec::new(),
+            vars: Vec::new(),
+            loop_index: None,
+            index: 0,
+            done: false,
+            marker: PhantomData,
+        };
+
+        this.input.expect(b'{')?;
+
+        if !this.read_sections(true)? {
+            return Err(ItfError::missing_field("states"));
+        }
+
+        Ok(this)
+    }
+
+    pub fn meta(&self) -> &TraceMeta {
+        &self.meta
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn vars(&self) -> &[String] {
+        &self.vars
+    }
+
+    pub fn loop_index(&self) -> Option<u64> {
+        self.loop_index
+    }
+
+    /// Reads the transitions of the trace, ie. the pairs of consecutive states,
+    /// reading each state once.
+    ///
+    /// A trace with less than two states has no transitions.
+    pub fn transitions(self) -> impl Iterator<Item = Result<(State<S>, State<S>), ItfError>>
+    where
+        S: Clone,
+    {
+        let mut previous = None;
+
+        self.filter_map(move |state| match state {
+            Ok(state) => previous
+                .replace(state.clone())
+                .map(|previous| Ok((previous, state))),
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Reads the sections of the trace until the start of the `states` array,
+    /// or until the end of the trace. Returns whether `states` was found.
+    fn read_sections(&mut self, mut first: bool) -> Result<bool, ItfError> {
+        let mut buf = Vec::new();
+
+        loop {
+            match self.input.peek()? {
+                Some(b'}') => {
+                    self.input.next()?;
+                    self.input.expect_end()?;
+                    return Ok(false);
+                }
+                Some(b',') if !first => {
+                    self.input.next()?;
+                }
+                _ if first => {}
+                _ => return Err(self.input.syntax(OBJECT_COMMA_OR_END).into()),
+            }
+
+            first = false;
+
+            buf.clear();
+            self.input.read_string(&mut buf)?;
+            let key: String = serde_json::from_slice(&buf)?;
+
+            self.input.expect(b':')?;
+
+            if key == "states" {
+                self.input.expect(b'[')?;
+                return Ok(true);
+            }
+
+            buf.clear();
+            self.input.read_value(&mut buf)?;
+
+            match key.as_str() {
+                "#meta" => self.meta = crate::from_slice(&buf)?,
+                "params" => self.params = crate::from_slice(&buf)?,
+                "vars" => self.vars = crate::from_slice(&buf)?,
+                "loop" => self.loop_index = crate::from_slice(&buf)?,
+                _ => {}
+            }
+        }
+    }
+
+    fn next_state(&mut self) -> Result<Option<State<S>>, ItfError> {
+        match self.input.peek()? {
+            Some(b']') => {
+                self.input.next()?;
+                self.read_sections(false)?;
+                return Ok(None);
+            }
+            Some(b',') if self.index > 0 => {
+                self.input.next()?;
+            }
+            _ if self.index == 0 => {}
+            _ => return Err(self.input.syntax(LIST_COMMA_OR_END).into()),
+        }
+
+        let mut buf = Vec::new();
+        self.input.read_value(&mut buf)?;
+
+        let value = serde_json::from_slice(&buf)?;
+        let state = crate::from_value(&value)
+            .map_err(|e| located::<ItfError>(("states", self.index), e))?;
+
+        self.index += 1;
+        Ok(Some(state))
+    }
+}
+
+impl<S, R> Iterator for TraceReader<S, R>
+where
+    S: DeserializeOwned,
+    R: Read,
+{
+    type Item = Result<State<S>, ItfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let state = self.next_state().transpose();
+        if !matches!(state, Some(Ok(_))) {
+            self.done = true;
+        }
+        state
+    }
+}
+
+// JSON texts which `serde_json` rejects with the syntax error of the same name
+// when they are followed by an unexpected character.
+const OBJECT_COMMA_OR_END: &str = r#"{"":0"#;
+const LIST_COMMA_OR_END: &str = "[0";
+const COLON: &str = r#"{"""#;
+const KEY_MUST_BE_A_STRING: &str = "{";
+const TRAILING_CHARACTERS: &str = "0";
+const EXPECTED_VALUE: &str = "";
+
+/// Splits a stream of JSON into values, without decoding them.
+struct Input<R> {
+    bytes: Bytes<BufReader<R>>,
+    peeked: Option<u8>,
+
+    /// The line of the last byte read, starting at 1, and its column,
+    /// in bytes, as `serde_json` reports them.
+    line: usize,
+    column: usize,
+}
+
+impl<R> Input<R>
+where
+    R: Read,
+{
+    fn new(reader: R) -> Self {
+        Self {
+            bytes: BufReader::new(reader).bytes(),
+            peeked: None,
+            line: 1,
+            column: 0,
+        }
+    }
+
+    /// Returns the syntax error which `serde_json` reports for `prefix` followed by
+    /// the next byte of the input, positioned at that byte in the input.
+    fn syntax(&self, prefix: &str) -> Error {
+        let (line, column) = (self.line, self.column + 1);
+
+        // Whitespace moves the unexpected character to its position in the input
+        let (newlines, spaces) = match line {
+            1 => (0, (column - 1).saturating_sub(prefix.len())),
+            _ => (line - 1, column - 1),
+        };
+
+        let json = prefix
+            .as_bytes()
+            .chain(io::repeat(b'\n').take(newlines as u64))
+            .chain(io::repeat(b' ').take(spaces as u64))
+            .chain(&b"!"[..]);
+
+        serde_json::from_reader::<_, IgnoredAny>(json).expect_err("the JSON is malformed")
+    }
+
+    /// Returns the error for a value which is not the object or the array
+    /// `expected` by the reader, positioned at `first`, its first byte.
+    fn invalid_type(&self, first: u8, expected: u8) -> Error {
+        let unexpected = match first {
+            b'"' => "string",
+            b'[' => "sequence",
+            b'{' => "map",
+            b't' | b'f' => "boolean",
+            b'n' => "null",
+            _ => "number",
+        };
+        let expected = if expected == b'{' {
+            "a map"
+        } else {
+            "a sequence"
+        };
+
+        Error::custom(format_args!(
+            "invalid type: {unexpected}, expected {expected} at line {} column {}",
+            self.line,
+            self.column + 1,
+        ))
+    }
+
+    /// Returns the next byte without consuming it.
+    fn peek_byte(&mut self) -> JsonResult<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = self.bytes.next().transpose().map_err(Error::io)?;
+        }
+        Ok(self.peeked)
+    }
+
+    fn next_byte(&mut self) -> JsonResult<Option<u8>> {
+        let b = match self.peeked.take() {
+            Some(b) => Some(b),
+            None => self.bytes.next().transpose().map_err(Error::io)?,
+        };
+
+        match b {
+            Some(b'\n') => {
+                self.line += 1;
+                self.column = 0;
+            }
+            Some(_) => self.column += 1,
+            None => {}
+        }
+
+        Ok(b)
+    }
+
+    /// Returns the next non-whitespace byte without consuming it.
+    fn peek(&mut self) -> JsonResult<Option<u8>> {
+        while let Some(b) = self.peek_byte()? {
+            if !b.is_ascii_whitespace() {
+                return Ok(Some(b));
+            }
+            self.next_byte()?;
+        }
+        Ok(None)
+    }
+
+    fn next(&mut self) -> JsonResult<Option<u8>> {
+        self.peek()?;
+        self.next_byte()
+    }
+
+    /// Consumes the next non-whitespace byte, which is `expected`:
+    /// a `:`, or the start of an object or of an array.
+    fn expect(&mut self, expected: u8) -> JsonResult<()> {
+        match self.peek()? {
+            Some(b) if b == expected => self.next_byte().map(drop),
+            Some(_) if expected == b':' => Err(self.syntax(COLON)),
+            Some(b @ (b'"' | b'[' | b'{' | b't' | b'f' | b'n' | b'-' | b'0'..=b'9')) => {
+                Err(self.invalid_type(b, expected))
+            }
+            Some(_) => Err(self.syntax(EXPECTED_VALUE)),
+            None => Err(self.eof()),
+        }
+    }
+
+    fn expect_end(&mut self) -> JsonResult<()> {
+        match self.peek()? {
+            None => Ok(()),
+            Some(_) => Err(self.syntax(TRAILING_CHARACTERS)),
+        }
+    }
+
+    fn eof(&self) -> Error {
+        Error::io(io::ErrorKind::UnexpectedEof.into())
+    }
+
+    /// Copies the next string, including its quotes, to `buf`.
+    fn read_string(&mut self, buf: &mut Vec<u8>) -> JsonResult<()> {
+        if self.peek()? != Some(b'"') {
+            return Err(self.syntax(KEY_MUST_BE_A_STRING));
+        }
+
+        buf.extend(self.next_byte()?);
+        self.read_string_rest(buf)
+    }
+
+    fn read_string_rest(&mut self, buf: &mut Vec<u8>) -> JsonResult<()> {
+        loop {
+            let b = self.next_byte()?.ok_or_else(|| self.eof())?;
+            buf.push(b);
+
+            match b {
+                b'"' => return Ok(()),
+                b'\\' => buf.push(self.next_byte()?.ok_or_else(|| self.eof())?),
+                _ => {}
+            }
+        }
+    }
+
+    /// Copies the next value to `buf`.
+    fn read_value(&mut self, buf: &mut Vec<u8>) -> JsonResult<()> {
+        let mut depth = 0_usize;
+
+        loop {
+            let b = match self.peek_byte()? {
+                Some(b) if depth == 0 && matches!(b, b',' | b']' | b'}') => break,
+                Some(b) if depth == 0 && b.is_ascii_whitespace() && !buf.is_empty() => break,
+                Some(b) => b,
+                None if depth == 0 && !buf.is_empty() => break,
+                None => return Err(self.eof()),
+            };
+
+            self.next_byte()?;
+
+            match b {
+                b'"' => {
+                    buf.push(b);
+                    self.read_string_rest(buf)?;
+                }
+                b'{' | b'[' => {
+                    buf.push(b);
+                    depth += 1;
+                }
+                b'}' | b']' => {
+                    buf.push(b);
+                    depth -= 1;
+                }
+                b if b.is_ascii_whitespace() => {}
+                b => buf.push(b),
+            }
+
+            if depth == 0 && matches!(b, b'"' | b'}' | b']') {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ItfBigInt, ItfError, ItfSet};
+
+    #[derive(Clone, Debug, serde::Deserialize)]
+    struct Counter {
+        count: ItfBigInt,
+        seen: ItfSet<i64>,
+    }
+
+    const TRACE: &str = r##"{
+        "#meta": { "format": "ITF" },
+        "params": [],
+        "vars": ["count", "seen"],
+        "states": [
+            { "#meta": { "index": 0 }, "count": 0, "seen": { "#set": [] } },
+            { "#meta": { "index": 1 }, "count": { "#bigint": "1" }, "seen": { "#set": [0] } },
+            { "#meta": { "index": 2 }, "count": 2, "seen": { "#set": [0, 1] } }
+        ],
+        "loop": 1
+    }"##;
+
+    #[test]
+    fn read_states() {
+        let mut reader = TraceReader::<Counter, _>::new(TRACE.as_bytes()).unwrap();
+
+        assert_eq!(reader.meta().format.as_deref(), Some("ITF"));
+        assert_eq!(reader.vars(), ["count", "seen"]);
+        assert_eq!(reader.loop_index(), None);
+
+        let states = reader
+            .by_ref()
+            .collect::<Result<Vec<_>, ItfError>>()
+            .unwrap();
+
+        assert_eq!(states.len(), 3);
+        assert_eq!(states[1].meta.index, Some(1));
+        assert_eq!(states[2].value.count, ItfBigInt::from(2));
+        assert!(states[2].value.seen.contains(&1));
+
+        assert_eq!(reader.loop_index(), Some(1));
+    }
+
+    #[test]
+    fn same_as_trace_from_str() {
+        let trace = crate::trace_from_str::<Counter>(TRACE).unwrap();
+        let reader = TraceReader::<Counter, _>::new(TRACE.as_bytes()).unwrap();
+
+        let counts = reader
+            .map(|state| state.unwrap().value.count)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            counts,
+            trace
+                .states
+                .into_iter()
+                .map(|state| state.value.count)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn transitions() {
+        let reader = TraceReader::<Counter, _>::new(TRACE.as_bytes()).unwrap();
+
+        let steps = reader
+            .transitions()
+            .map(|t| t.map(|(from, to)| (from.value.count, to.value.count)))
+            .collect::<Result<Vec<_>, ItfError>>()
+            .unwrap();
+
+        assert_eq!(
+            steps,
+            [
+                (ItfBigInt::from(0), ItfBigInt::from(1)),
+                (ItfBigInt::from(1), ItfBigInt::from(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_states() {
+        let reader = TraceReader::<Counter, _>::new(r##"{"states":[]}"##.as_bytes()).unwrap();
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[test]
+    fn missing_states() {
+        let err = TraceReader::<Counter, _>::new(r##"{"vars": []}"##.as_bytes())
+            .err()
+            .unwrap();
+
+        assert_eq!(err.to_string(), "missing field `states`");
+    }
+
+    #[test]
+    fn invalid_state() {
+        let json = r##"{"states": [{"count": 0, "seen": {"#set": []}}, {"count": "x", "seen": {"#set": []}}]}"##;
+        let mut reader = TraceReader::<Counter, _>::new(json.as_bytes()).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.path(), Some("states[1]"));
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let err = |json: &str| match TraceReader::<Counter, _>::new(json.as_bytes()) {
+            Ok(reader) => reader.filter_map(Result::err).next().unwrap(),
+            Err(e) => e,
+        };
+
+        let malformed = [
+            r##"{"#meta": {} "states": []}"##,
+            r##"{"#meta" {}, "states": []}"##,
+            r##"{1: 2}"##,
+            "{\n  \"states\": [\n    {\"count\": 0, \"seen\": {\"#set\": []}}\n    {}\n  ]\n}",
+            r##"{"states": []} x"##,
+            r##"{"states": [], "vars": [] "loop": 0}"##,
+            r##"x"##,
+        ];
+
+        for json in malformed {
+            let expected = serde_json::from_str::<serde_json::Value>(json).unwrap_err();
+
+            match err(json) {
+                ItfError::Json(e) => {
+                    assert!(e.is_syntax(), "{json}: {e}");
+                    assert_eq!(e.to_string(), expected.to_string(), "{json}");
+                }
+                e => panic!("{json}: expected a JSON error, got {e:?}"),
+            }
+        }
+
+        assert_eq!(
+            err(r##"[1]"##).to_string(),
+            "invalid type: sequence, expected a map at line 1 column 1"
+        );
+        assert_eq!(
+            err(r##"{"states": 2}"##).to_string(),
+            "invalid type: number, expected a sequence at line 1 column 12"
+        );
+    }
+}
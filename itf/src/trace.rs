@@ -1,17 +1,61 @@
-use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io::Read,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
 
-use crate::{StateMeta, TraceMeta};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
+use crate::{
+    error::located,
+    util::serde::{elements, struct_fields, value::ValueDeserializer},
+    Format, ItfError, LazyTrace, RandSeed, StateMeta, TraceMeta,
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct State<S> {
-    #[serde(rename = "#meta")]
     pub meta: StateMeta,
-
-    #[serde(flatten)]
     pub value: S,
 }
 
+/// The `#meta` section of the state, if any, is decoded into `meta`,
+/// and its variables into `value`.
+///
+/// If `S` has a field for the `#meta` section, eg. one marked with
+/// `#[itf(meta)]` when deriving `ItfState`, the `#meta` section is
+/// also decoded into that field.
+impl<'de, S> Deserialize<'de> for State<S>
+where
+    S: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut vars = serde_json::Map::deserialize(deserializer)?;
+
+        let wants_meta = struct_fields::of::<S>().map_or(false, |fields| fields.contains(&"#meta"));
+        let meta = if wants_meta {
+            vars.get("#meta").cloned()
+        } else {
+            vars.remove("#meta")
+        };
+
+        let meta = match meta {
+            Some(meta) => StateMeta::deserialize(ValueDeserializer::<D::Error>::new(&meta))
+                .map_err(|e| located("#meta", e))?,
+            None => StateMeta::default(),
+        };
+
+        let value = S::deserialize(ValueDeserializer::new(&serde_json::Value::Object(vars)))?;
+
+        Ok(Self { meta, value })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(bound(deserialize = "S: DeserializeOwned"))]
 pub struct Trace<S> {
     #[serde(rename = "#meta")]
     pub meta: TraceMeta,
@@ -25,9 +69,18 @@ pub struct Trace<S> {
     #[serde(default, rename = "loop")]
     pub loop_index: Option<u64>,
 
+    #[serde(deserialize_with = "states")]
     pub states: Vec<State<S>>,
 }
 
+fn states<'de, D, S>(deserializer: D) -> Result<Vec<State<S>>, D::Error>
+where
+    D: Deserializer<'de>,
+    S: DeserializeOwned,
+{
+    elements::deserialize("states", deserializer)
+}
+
 impl<S> Default for Trace<S> {
     fn default() -> Self {
         Self {
@@ -40,10 +93,652 @@ impl<S> Default for Trace<S> {
     }
 }
 
+impl<S> Trace<S> {
+    /// Returns the TLA+ types of the variables, as inferred by the model checker,
+    /// or `None` if the `#meta` section of the trace does not include them.
+    pub fn var_types(&self) -> Option<&HashMap<String, String>> {
+        if self.meta.var_types.is_empty() {
+            None
+        } else {
+            Some(&self.meta.var_types)
+        }
+    }
+
+    /// Returns the seed of the random generator which produced the trace,
+    /// so that it can be generated again, if it is recorded in the `#meta` section.
+    pub fn rand_seed(&self) -> Option<u64> {
+        self.meta.rand_seed.as_ref().and_then(RandSeed::value)
+    }
+
+    /// Iterates over the states of the trace, along with their index.
+    pub fn iter_states(&self) -> impl Iterator<Item = (usize, &State<S>)> {
+        self.states.iter().enumerate()
+    }
+
+    /// Iterates over the transitions of the trace, ie. the pairs of consecutive
+    /// states, eg. to check properties of each step. A stuttering step
+    /// is one whose states have equal values.
+    ///
+    /// A trace with less than two states has no transitions.
+    pub fn iter_transitions(&self) -> impl Iterator<Item = (&State<S>, &State<S>)> {
+        self.states.windows(2).map(|pair| (&pair[0], &pair[1]))
+    }
+
+    /// Returns the values of the states of the trace, dropping the rest.
+    pub fn into_states(self) -> Vec<S> {
+        self.states.into_iter().map(|state| state.value).collect()
+    }
+
+    /// Splits the trace into its `#meta`, `params`, `vars`, `loop` and `states` sections.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(
+        self,
+    ) -> (
+        TraceMeta,
+        Vec<String>,
+        Vec<String>,
+        Option<u64>,
+        Vec<State<S>>,
+    ) {
+        (
+            self.meta,
+            self.params,
+            self.vars,
+            self.loop_index,
+            self.states,
+        )
+    }
+
+    /// Extracts the states in `steps` as a standalone trace, whose initial
+    /// state is the first of them, eg. to bisect a failure.
+    ///
+    /// The `#meta.index` of the states, if any, and the `loop` index are
+    /// shifted accordingly, the latter being dropped if the loop does not
+    /// start within `steps`. The other sections of the trace are kept as is.
+    ///
+    /// Fails if `steps` is empty or goes past the last state.
+    pub fn sub_trace<R>(&self, steps: R) -> Result<Self, StepsOutOfRange>
+    where
+        S: Clone,
+        R: RangeBounds<usize>,
+    {
+        let len = self.states.len();
+
+        let start = match steps.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+
+        let end = match steps.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+
+        if start >= end || end > len {
+            return Err(StepsOutOfRange { start, end, len });
+        }
+
+        let states = self.states[start..end]
+            .iter()
+            .enumerate()
+            .map(|(i, state)| {
+                let mut state = state.clone();
+                if state.meta.index.is_some() {
+                    state.meta.index = Some(i as u64);
+                }
+                state
+            })
+            .collect();
+
+        let loop_index = self
+            .loop_index
+            .and_then(|index| index.checked_sub(start as u64))
+            .filter(|&index| index < (end - start) as u64);
+
+        Ok(Self {
+            meta: self.meta.clone(),
+            params: self.params.clone(),
+            vars: self.vars.clone(),
+            loop_index,
+            states,
+        })
+    }
+
+    /// Decodes a trace from a slice of bytes of JSON.
+    ///
+    /// If a value of the trace fails to decode, the error includes it.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ItfError>
+    where
+        S: DeserializeOwned,
+    {
+        Self::from_value(&serde_json::from_slice(bytes)?)
+    }
+
+    /// Decodes a trace from a reader of JSON, eg. a file or a decompressing
+    /// reader such as `flate2::read::GzDecoder` for gzipped traces.
+    ///
+    /// The reader is not buffered; wrap it in a [`std::io::BufReader`] if needed.
+    pub fn from_reader<R>(reader: R) -> Result<Self, ItfError>
+    where
+        S: DeserializeOwned,
+        R: Read,
+    {
+        Self::from_value(&serde_json::from_reader(reader)?)
+    }
+
+    /// Decodes a trace from a JSON value.
+    ///
+    /// If a value of the trace fails to decode, the error includes it.
+    pub fn from_value(json: &serde_json::Value) -> Result<Self, ItfError>
+    where
+        S: DeserializeOwned,
+    {
+        crate::from_value(json).map_err(|e| e.with_snippet(json))
+    }
+
+    /// Decodes a trace from a JSON value, along with the values of the
+    /// parameters declared in its `params` section, eg. the constants of the spec.
+    ///
+    /// Parameters have the same value in every state of the trace, so they
+    /// are decoded from the first state, before its variables are decoded into `S`.
+    ///
+    /// ```
+    /// use itf::{ItfSet, Trace};
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Params {
+    ///     #[serde(rename = "PROCS")]
+    ///     procs: ItfSet<String>,
+    /// }
+    ///
+    /// let json = serde_json::json!({
+    ///     "#meta": {},
+    ///     "params": ["PROCS"],
+    ///     "vars": ["x"],
+    ///     "states": [{ "PROCS": { "#set": ["p1", "p2"] }, "x": 0 }],
+    /// });
+    ///
+    /// let (trace, params) = Trace::<serde_json::Value>::from_value_with_params::<Params>(json)?;
+    /// assert_eq!(params.procs.len(), 2);
+    /// assert_eq!(trace.states.len(), 1);
+    /// # Ok::<(), itf::ItfError>(())
+    /// ```
+    pub fn from_value_with_params<P>(json: serde_json::Value) -> Result<(Self, P), ItfError>
+    where
+        S: DeserializeOwned,
+        P: DeserializeOwned,
+    {
+        let names = match json.get("params") {
+            Some(params) => crate::from_value(params)?,
+            None => Vec::new(),
+        };
+
+        let first = json.get("states").and_then(|states| states.get(0));
+        let params = decode_params(&names, first)?;
+
+        let trace = Self::from_value(&json)?;
+        Ok((trace, params))
+    }
+
+    /// Decodes a trace produced by the given model checker, or by the one
+    /// detected from its `#meta` section if `format` is `None`.
+    /// Traces whose producer cannot be detected are decoded as Apalache traces.
+    ///
+    /// Unlike Apalache, Quint may omit the `#meta` section of the trace,
+    /// in which case it is defaulted.
+    pub fn parse_with(format: Option<Format>, mut json: serde_json::Value) -> Result<Self, ItfError>
+    where
+        S: DeserializeOwned,
+    {
+        let format = match format {
+            Some(format) => format,
+            None => json
+                .get("#meta")
+                .and_then(|meta| crate::from_value(meta).ok())
+                .and_then(|meta| Format::detect(&meta))
+                .unwrap_or(Format::Apalache),
+        };
+
+        if format == Format::Quint {
+            if let serde_json::Value::Object(trace) = &mut json {
+                trace
+                    .entry("#meta")
+                    .or_insert_with(|| serde_json::Value::Object(Default::default()));
+            }
+        }
+
+        Self::from_value(&json)
+    }
+}
+
+/// Decodes the values of the parameters `names` from `first`, the first state of a trace.
+pub(crate) fn decode_params<P>(
+    names: &[String],
+    first: Option<&serde_json::Value>,
+) -> Result<P, ItfError>
+where
+    P: DeserializeOwned,
+{
+    let first = first.and_then(serde_json::Value::as_object);
+
+    let params = names
+        .iter()
+        .filter_map(|name| Some((name.clone(), first?.get(name)?.clone())))
+        .collect::<serde_json::Map<_, _>>();
+
+    crate::from_value(&serde_json::Value::Object(params)).map_err(|e| located(("states", 0), e))
+}
+
+impl<S> FromStr for Trace<S>
+where
+    S: DeserializeOwned,
+{
+    type Err = ItfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_value(&serde_json::from_str(s)?)
+    }
+}
+
+impl<S> Trace<S>
+where
+    S: DeserializeOwned,
+{
+    /// Checks that the fields of the state type `S` are exactly the variables
+    /// declared in the `vars` section of the trace.
+    ///
+    /// If `S` does not deserialize as a plain struct, eg. because it uses
+    /// `#[serde(flatten)]`, its fields cannot be known and the check passes.
+    pub fn validate_vars(&self) -> Result<(), VarsMismatch> {
+        let fields = match struct_fields::of::<S>() {
+            Some(fields) => fields,
+            None => return Ok(()),
+        };
+
+        // The field for the `#meta` section, if any, is not a variable
+        let fields = fields
+            .iter()
+            .copied()
+            .filter(|field| *field != "#meta")
+            .collect::<Vec<_>>();
+
+        let unmodeled = self
+            .vars
+            .iter()
+            .filter(|var| !fields.contains(&var.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let undeclared = fields
+            .iter()
+            .filter(|field| !self.vars.iter().any(|var| var == *field))
+            .map(|field| field.to_string())
+            .collect::<Vec<_>>();
+
+        if unmodeled.is_empty() && undeclared.is_empty() {
+            Ok(())
+        } else {
+            Err(VarsMismatch {
+                unmodeled,
+                undeclared,
+            })
+        }
+    }
+}
+
+/// The variables of a trace and the fields of its state type do not match.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "trace variables do not match the state type \
+     (not modeled: {unmodeled:?}, not declared: {undeclared:?})"
+)]
+pub struct VarsMismatch {
+    /// Variables declared by the trace but not modeled by the state type
+    pub unmodeled: Vec<String>,
+
+    /// Fields of the state type not declared as variables by the trace
+    pub undeclared: Vec<String>,
+}
+
+/// A range of steps of a trace, given as `start..end`, is empty
+/// or goes past its last state.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("steps `{start}..{end}` are not a non-empty range of the {len} states of the trace")]
+pub struct StepsOutOfRange {
+    pub start: usize,
+    pub end: usize,
+    pub len: usize,
+}
+
+impl<S> Trace<S> {
+    /// Checks that the `#meta.index` of each state, if any, is its position in the trace.
+    ///
+    /// The other checks of [`TraceCheck`] are on the variables of the states as
+    /// found in the trace, whatever the state type models of them, so they are
+    /// only run on a [`LazyTrace`](crate::LazyTrace), see [`LazyTrace::validate`](crate::LazyTrace::validate).
+    ///
+    /// Returns all the issues found, in the order of the states.
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        TraceCheck::default().validate(self)
+    }
+}
+
+impl<S> Trace<S>
+where
+    S: Serialize,
+{
+    /// Returns whether the trace is equivalent to `other`, as compared
+    /// by the default [`TraceCmp`].
+    pub fn equivalent(&self, other: &Self) -> bool
+    where
+        S: PartialEq,
+    {
+        TraceCmp::default().equivalent(self, other)
+    }
+}
+
+/// Configures which parts of two traces [`TraceCmp::equivalent`] compares,
+/// eg. to check that a refactored generator still produces the same traces.
+///
+/// The states are always compared in order, as [`diff`](crate::diff) does,
+/// so that the order of the elements of a `#set` and of the entries of a
+/// `#map` is irrelevant. States which fail to serialize, eg. because they
+/// have a map with non-string keys, are compared with `PartialEq` instead.
+/// The `params`, `vars` and `loop` sections are always compared.
+///
+/// By default, the `#meta` section of the traces is compared except for its
+/// `description` and `timestamp`, which typically change on every run, and
+/// the `#meta.index` of the states is compared.
+///
+/// ```
+/// use itf::{Trace, TraceCmp};
+///
+/// let a: Trace<()> = serde_json::from_str(r##"{ "#meta": { "description": "1st run" }, "states": [] }"##)?;
+/// let b: Trace<()> = serde_json::from_str(r##"{ "#meta": { "description": "2nd run" }, "states": [] }"##)?;
+///
+/// assert!(a.equivalent(&b));
+/// assert!(!TraceCmp::default().description(true).equivalent(&a, &b));
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceCmp {
+    meta: bool,
+    description: bool,
+    timestamp: bool,
+    indices: bool,
+}
+
+impl Default for TraceCmp {
+    fn default() -> Self {
+        Self {
+            meta: true,
+            description: false,
+            timestamp: false,
+            indices: true,
+        }
+    }
+}
+
+impl TraceCmp {
+    /// Compares the `#meta` section of the traces, apart from its
+    /// `description` and `timestamp`.
+    pub fn meta(mut self, compare: bool) -> Self {
+        self.meta = compare;
+        self
+    }
+
+    /// Compares the `#meta.description` of the traces.
+    pub fn description(mut self, compare: bool) -> Self {
+        self.description = compare;
+        self
+    }
+
+    /// Compares the `#meta.timestamp` of the traces.
+    pub fn timestamp(mut self, compare: bool) -> Self {
+        self.timestamp = compare;
+        self
+    }
+
+    /// Compares the `#meta.index` of the states.
+    pub fn indices(mut self, compare: bool) -> Self {
+        self.indices = compare;
+        self
+    }
+
+    /// Returns whether the traces are equivalent.
+    pub fn equivalent<S>(&self, a: &Trace<S>, b: &Trace<S>) -> bool
+    where
+        S: Serialize + PartialEq,
+    {
+        let rest = |meta: &TraceMeta| TraceMeta {
+            description: None,
+            timestamp: None,
+            ..meta.clone()
+        };
+
+        if (self.meta && rest(&a.meta) != rest(&b.meta))
+            || (self.description && a.meta.description != b.meta.description)
+            || (self.timestamp && a.meta.timestamp != b.meta.timestamp)
+        {
+            return false;
+        }
+
+        if a.params != b.params
+            || a.vars != b.vars
+            || a.loop_index != b.loop_index
+            || a.states.len() != b.states.len()
+        {
+            return false;
+        }
+
+        a.states.iter().zip(&b.states).all(|(x, y)| {
+            (!self.indices || x.meta.index == y.meta.index)
+                && crate::diff(&x.value, &y.value)
+                    .map_or_else(|_| x.value == y.value, |diff| diff.is_empty())
+        })
+    }
+}
+
+/// Configures which checks [`TraceCheck::validate_lazy`] runs, eg. to validate
+/// hand-written traces whose `#meta.index` was not kept up to date.
+///
+/// By default, all the checks of [`LazyTrace::validate`](crate::LazyTrace::validate) are run.
+/// [`TraceCheck::validate`] only runs the check on indices, as the others need
+/// the variables of the states as found in the trace.
+///
+/// ```
+/// use itf::{LazyTrace, TraceCheck};
+///
+/// let json = r##"{ "#meta": {}, "vars": ["x"], "states": [{ "#meta": { "index": 1 }, "x": 1 }] }"##;
+/// let trace: LazyTrace<serde_json::Value> = serde_json::from_str(json)?;
+///
+/// assert!(trace.validate().is_err());
+/// assert!(TraceCheck::default().indices(false).validate_lazy(&trace).is_ok());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceCheck {
+    indices: bool,
+    vars: bool,
+    params: bool,
+}
+
+impl Default for TraceCheck {
+    fn default() -> Self {
+        Self {
+            indices: true,
+            vars: true,
+            params: true,
+        }
+    }
+}
+
+impl TraceCheck {
+    /// Checks that the `#meta.index` of each state, if any, is its position in the trace.
+    pub fn indices(mut self, check: bool) -> Self {
+        self.indices = check;
+        self
+    }
+
+    /// Checks that each state only has variables declared in the `vars` or `params` section.
+    pub fn vars(mut self, check: bool) -> Self {
+        self.vars = check;
+        self
+    }
+
+    /// Checks that each state has a value for every parameter declared in the `params` section.
+    pub fn params(mut self, check: bool) -> Self {
+        self.params = check;
+        self
+    }
+
+    /// Checks the `#meta.index` of the states of `trace`, as [`Trace::validate`] does.
+    pub fn validate<S>(&self, trace: &Trace<S>) -> Result<(), Vec<ValidationIssue>> {
+        let states = trace.states.iter().map(|state| (state.meta.index, None));
+
+        self.check(&trace.vars, &trace.params, states)
+    }
+
+    /// Checks the consistency of `trace` on the states as found in the trace,
+    /// as [`LazyTrace::validate`](crate::LazyTrace::validate) does.
+    pub fn validate_lazy<S>(&self, trace: &LazyTrace<S>) -> Result<(), Vec<ValidationIssue>> {
+        let states = trace.states.iter().map(|state| {
+            let index = state
+                .get("#meta")
+                .and_then(|meta| meta.get("index"))
+                .and_then(serde_json::Value::as_u64);
+
+            (index, state.as_object())
+        });
+
+        self.check(&trace.vars, &trace.params, states)
+    }
+
+    /// Runs the checks on the index of each state and, if known, on its variables,
+    /// along with its `#meta` section.
+    fn check<'a, I>(
+        &self,
+        vars: &[String],
+        params: &[String],
+        states: I,
+    ) -> Result<(), Vec<ValidationIssue>>
+    where
+        I: Iterator<
+            Item = (
+                Option<u64>,
+                Option<&'a serde_json::Map<String, serde_json::Value>>,
+            ),
+        >,
+    {
+        let mut issues = Vec::new();
+
+        for (i, (index, state)) in states.enumerate() {
+            if let Some(index) = index.filter(|_| self.indices) {
+                if index != i as u64 {
+                    issues.push(ValidationIssue::NonContiguousIndex { state: i, index });
+                }
+            }
+
+            let state = match state {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let names = state.keys().filter(|name| *name != "#meta");
+
+            for name in names.filter(|_| self.vars) {
+                if !vars.contains(name) && !params.contains(name) {
+                    issues.push(ValidationIssue::UndeclaredVariable {
+                        state: i,
+                        name: name.clone(),
+                    });
+                }
+            }
+
+            for name in params.iter().filter(|_| self.params) {
+                if !state.contains_key(name) {
+                    issues.push(ValidationIssue::MissingParam {
+                        state: i,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+/// A consistency issue in a trace, as found by [`Trace::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationIssue {
+    #[error("state {state} has index {index} in its `#meta` section")]
+    NonContiguousIndex { state: usize, index: u64 },
+
+    #[error("state {state} has variable `{name}`, which is not declared in `vars` or `params`")]
+    UndeclaredVariable { state: usize, name: String },
+
+    #[error("state {state} has no value for parameter `{name}`")]
+    MissingParam { state: usize, name: String },
+}
+
+impl ValidationIssue {
+    /// Returns the index of the offending state in the trace.
+    pub fn state(&self) -> usize {
+        match self {
+            Self::NonContiguousIndex { state, .. }
+            | Self::UndeclaredVariable { state, .. }
+            | Self::MissingParam { state, .. } => *state,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::{ItfBigInt, MbtState, RandSeed, Timestamp};
+
+    #[derive(Debug, Deserialize)]
+    struct Counter {
+        count: ItfBigInt,
+    }
+
+    fn quint_trace() -> serde_json::Value {
+        serde_json::json!({
+            "#meta": {
+                "format": "ITF",
+                "format-description": "https://apalache.informal.systems/docs/adr/015adr-trace.html",
+                "source": "counter.qnt",
+                "status": "ok",
+                "description": "Created by Quint on Mon Oct 14 2024 10:02:11 GMT+0200",
+                "timestamp": 1728892931714_u64,
+                "version": "0.22.1",
+            },
+            "vars": ["count", "mbt::actionTaken", "mbt::nondetPicks"],
+            "states": [
+                {
+                    "#meta": { "index": 0 },
+                    "count": { "#bigint": "0" },
+                    "mbt::actionTaken": "init",
+                    "mbt::nondetPicks": {},
+                },
+                {
+                    "#meta": { "index": 1 },
+                    "count": { "#bigint": "1" },
+                    "mbt::actionTaken": "step",
+                    "mbt::nondetPicks": {},
+                },
+            ],
+        })
+    }
+
     #[test]
     fn trace_default() {
         #[derive(Debug, PartialEq, Eq)]
@@ -56,4 +751,578 @@ mod tests {
         assert_eq!(t.loop_index, None);
         assert_eq!(t.states, Vec::<State<S>>::new());
     }
+
+    #[test]
+    fn state_meta() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct S {
+            x: i64,
+        }
+
+        let json = serde_json::json!({
+            "#meta": { "index": 3, "description": "after deposit" },
+            "x": 1,
+        });
+
+        let state: State<S> = serde_json::from_value(json).unwrap();
+        assert_eq!(state.meta.index, Some(3));
+        assert_eq!(state.meta.other["description"], "after deposit");
+        assert_eq!(state.value, S { x: 1 });
+    }
+
+    #[test]
+    fn state_without_meta() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct S {
+            x: i64,
+        }
+
+        let json = serde_json::json!({ "x": 1 });
+
+        let state: State<S> = serde_json::from_value(json).unwrap();
+        assert_eq!(state.meta, StateMeta::default());
+        assert_eq!(state.value, S { x: 1 });
+    }
+
+    #[test]
+    fn state_variables_decode_like_json() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        enum Action {
+            Init,
+            Deposit(u64),
+            Transfer { to: String },
+        }
+
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct S {
+            actions: Vec<Action>,
+            limit: Option<u64>,
+            names: (String, char),
+        }
+
+        let json = serde_json::json!({
+            "actions": ["Init", { "Deposit": 5 }, { "Transfer": { "to": "bob" } }],
+            "limit": null,
+            "names": ["alice", "b"],
+        });
+
+        let state: State<S> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(state.value, serde_json::from_value::<S>(json).unwrap());
+    }
+
+    #[test]
+    fn var_types() {
+        let json = serde_json::json!({
+            "#meta": {
+                "varTypes": { "x": "Int", "s": "Set(Str)" },
+                "producer": "apalache",
+            },
+            "vars": ["x", "s"],
+            "states": [],
+        });
+
+        let trace: Trace<()> = serde_json::from_value(json).unwrap();
+        let var_types = trace.var_types().unwrap();
+        assert_eq!(var_types["x"], "Int");
+        assert_eq!(var_types["s"], "Set(Str)");
+        assert_eq!(trace.meta.other["producer"], "apalache");
+
+        let json = serde_json::json!({ "#meta": {}, "states": [] });
+        let trace: Trace<()> = serde_json::from_value(json).unwrap();
+        assert_eq!(trace.var_types(), None);
+    }
+
+    #[test]
+    fn rand_seed() {
+        let seed = |seed: serde_json::Value| {
+            let json = serde_json::json!({ "#meta": { "rand_seed": seed }, "states": [] });
+            serde_json::from_value::<Trace<()>>(json).map(|trace| trace.rand_seed())
+        };
+
+        assert_eq!(seed(serde_json::json!(42)).unwrap(), Some(42));
+        assert_eq!(seed(serde_json::json!("42")).unwrap(), Some(42));
+        assert_eq!(seed(serde_json::json!("0x1c8")).unwrap(), Some(0x1c8));
+        assert_eq!(seed(serde_json::json!(null)).unwrap(), None);
+        assert!(seed(serde_json::json!("forty-two")).is_err());
+        assert!(seed(serde_json::json!("+42")).is_err());
+        assert!(seed(serde_json::json!("0x+1c8")).is_err());
+        assert!(seed(serde_json::json!("-1")).is_err());
+
+        let json = serde_json::json!({ "rand_seed": "0x01C8" });
+        let meta: TraceMeta = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(meta.rand_seed, Some(RandSeed::String("0x01C8".to_string())));
+        assert_eq!(serde_json::to_value(&meta).unwrap(), json);
+
+        let json = serde_json::json!({ "#meta": {}, "states": [] });
+        let trace: Trace<()> = serde_json::from_value(json).unwrap();
+        assert_eq!(trace.rand_seed(), None);
+    }
+
+    #[test]
+    fn producer_meta() {
+        let json = serde_json::json!({
+            "#meta": {
+                "timestamp": "2024-10-14T10:02:11Z",
+                "format-version": "1.0",
+                "tool": "quint",
+                "host": { "os": "linux" },
+            },
+            "states": [],
+        });
+
+        let trace: Trace<()> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            trace.meta.timestamp,
+            Some(Timestamp::String("2024-10-14T10:02:11Z".to_string()))
+        );
+        assert_eq!(trace.meta.format_version.as_deref(), Some("1.0"));
+        assert_eq!(trace.meta.tool.as_deref(), Some("quint"));
+        assert_eq!(trace.meta.other["host"], json["#meta"]["host"]);
+
+        let meta = serde_json::to_value(&trace.meta).unwrap();
+        assert_eq!(meta["timestamp"], json["#meta"]["timestamp"]);
+        assert_eq!(meta["format-version"], "1.0");
+        assert_eq!(meta["host"], json["#meta"]["host"]);
+    }
+
+    #[test]
+    fn sub_trace() {
+        let json = serde_json::json!({
+            "#meta": { "source": "counter.tla" },
+            "vars": ["x"],
+            "loop": 2,
+            "states": (0..5).map(|x| serde_json::json!({ "#meta": { "index": x }, "x": x })).collect::<Vec<_>>(),
+        });
+
+        let trace: Trace<HashMap<String, i64>> = serde_json::from_value(json).unwrap();
+
+        let sub = trace.sub_trace(1..=3).unwrap();
+        assert_eq!(sub.meta, trace.meta);
+        assert_eq!(sub.loop_index, Some(1));
+        assert_eq!(sub.states.len(), 3);
+        assert_eq!(sub.states[0].value["x"], 1);
+        assert_eq!(sub.states[2].meta.index, Some(2));
+        assert_eq!(sub.validate(), Ok(()));
+
+        assert_eq!(trace.sub_trace(3..).unwrap().loop_index, None);
+        assert_eq!(trace.sub_trace(..).unwrap(), trace);
+
+        assert_eq!(
+            trace.sub_trace(3..6),
+            Err(StepsOutOfRange {
+                start: 3,
+                end: 6,
+                len: 5
+            })
+        );
+        assert_eq!(
+            trace.sub_trace(2..2).unwrap_err().to_string(),
+            "steps `2..2` are not a non-empty range of the 5 states of the trace"
+        );
+    }
+
+    #[test]
+    fn iter_transitions() {
+        let json = serde_json::json!({
+            "#meta": {},
+            "vars": ["x"],
+            "states": [{ "x": 1 }, { "x": 1 }, { "x": 2 }],
+        });
+
+        let trace: Trace<HashMap<String, i64>> = serde_json::from_value(json).unwrap();
+
+        let indices = trace.iter_states().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(indices, [0, 1, 2]);
+
+        let stutters = trace
+            .iter_transitions()
+            .map(|(from, to)| from.value == to.value)
+            .collect::<Vec<_>>();
+        assert_eq!(stutters, [true, false]);
+
+        let single = trace.sub_trace(..1).unwrap();
+        assert_eq!(single.iter_transitions().count(), 0);
+    }
+
+    #[test]
+    fn equivalent() {
+        let trace = |description: &str, index: u64, set: serde_json::Value| {
+            let json = serde_json::json!({
+                "#meta": { "source": "counter.tla", "description": description },
+                "vars": ["s"],
+                "states": [
+                    { "#meta": { "index": 0 }, "s": { "#set": [] } },
+                    { "#meta": { "index": index }, "s": set },
+                ],
+            });
+            serde_json::from_value::<Trace<crate::ItfValue>>(json).unwrap()
+        };
+
+        let a = trace("1st run", 1, serde_json::json!({ "#set": [1, 2] }));
+        let b = trace("2nd run", 1, serde_json::json!({ "#set": [2, 1] }));
+        assert!(a.equivalent(&b));
+        assert!(!TraceCmp::default().description(true).equivalent(&a, &b));
+
+        let c = trace("1st run", 7, serde_json::json!({ "#set": [1, 2] }));
+        assert!(!a.equivalent(&c));
+        assert!(TraceCmp::default().indices(false).equivalent(&a, &c));
+
+        let d = trace("1st run", 1, serde_json::json!({ "#set": [1] }));
+        assert!(!a.equivalent(&d));
+
+        let mut e = a.clone();
+        e.meta.source = Some("other.tla".to_string());
+        assert!(!a.equivalent(&e));
+        assert!(TraceCmp::default().meta(false).equivalent(&a, &e));
+    }
+
+    #[test]
+    fn equivalent_unserializable() {
+        // Maps with non-string keys fail to serialize to JSON
+        let trace = |x: i64| Trace {
+            states: vec![State {
+                meta: StateMeta::default(),
+                value: HashMap::from([((0, 0), x)]),
+            }],
+            ..Trace::default()
+        };
+
+        assert!(trace(1).equivalent(&trace(1)));
+        assert!(!trace(1).equivalent(&trace(2)));
+    }
+
+    #[test]
+    fn into_states_parts() {
+        let json = serde_json::json!({
+            "#meta": { "source": "counter.tla" },
+            "params": ["N"],
+            "vars": ["x"],
+            "loop": 1,
+            "states": [{ "N": 2, "x": 1 }, { "N": 2, "x": 2 }],
+        });
+
+        let trace: Trace<serde_json::Value> = serde_json::from_value(json).unwrap();
+
+        let (meta, params, vars, loop_index, states) = trace.clone().into_parts();
+        assert_eq!(meta.source.as_deref(), Some("counter.tla"));
+        assert_eq!(params, ["N"]);
+        assert_eq!(vars, ["x"]);
+        assert_eq!(loop_index, Some(1));
+        assert_eq!(states.len(), 2);
+
+        let states = trace.into_states();
+        assert_eq!(states[1]["x"], 2);
+    }
+
+    #[test]
+    fn from_str_slice_reader() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct S {
+            x: i64,
+        }
+
+        let json = r##"{ "#meta": {}, "vars": ["x"], "states": [{ "x": 1 }] }"##;
+
+        let trace: Trace<S> = json.parse().unwrap();
+        assert_eq!(trace.states[0].value, S { x: 1 });
+
+        let trace = Trace::<S>::from_slice(json.as_bytes()).unwrap();
+        assert_eq!(trace.states[0].value, S { x: 1 });
+
+        // Any reader works, eg. one which yields the trace in chunks
+        let (head, tail) = json.split_at(20);
+        let trace = Trace::<S>::from_reader(head.as_bytes().chain(tail.as_bytes())).unwrap();
+        assert_eq!(trace.states[0].value, S { x: 1 });
+
+        let err =
+            Trace::<S>::from_slice(br##"{ "#meta": {}, "states": [{ "x": "1" }] }"##).unwrap_err();
+        assert_eq!(err.path(), Some("states[0]"));
+        assert_eq!(err.snippet(), Some(&serde_json::json!({ "x": "1" })));
+    }
+
+    #[test]
+    fn from_value_with_params() {
+        use crate::{ItfBigInt, ItfSet};
+
+        #[derive(Debug, Deserialize)]
+        struct Params {
+            #[serde(rename = "N")]
+            n: ItfBigInt,
+            #[serde(rename = "PROCS")]
+            procs: ItfSet<String>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Counter {
+            count: i64,
+        }
+
+        let json = serde_json::json!({
+            "#meta": {},
+            "params": ["N", "PROCS"],
+            "vars": ["count"],
+            "states": [
+                { "N": 3, "PROCS": { "#set": ["p1", "p2"] }, "count": 0 },
+                { "N": 3, "PROCS": { "#set": ["p1", "p2"] }, "count": 1 },
+            ],
+        });
+
+        let (trace, params) =
+            Trace::<Counter>::from_value_with_params::<Params>(json.clone()).unwrap();
+        assert_eq!(params.n, ItfBigInt::from(3));
+        assert!(params.procs.contains("p2"));
+        assert_eq!(trace.states[1].value.count, 1);
+
+        let mut json = json;
+        json["params"] = serde_json::json!(["N"]);
+        let err = Trace::<Counter>::from_value_with_params::<Params>(json).unwrap_err();
+        assert_eq!(err.path(), Some("states[0]"));
+    }
+
+    #[test]
+    fn validate() {
+        let json = serde_json::json!({
+            "#meta": {},
+            "params": ["N"],
+            "vars": ["x"],
+            "states": [
+                { "#meta": { "index": 0 }, "N": 3, "x": 1 },
+                { "#meta": { "index": 2 }, "N": 3, "x": 2 },
+                { "x": 3, "y": 0 },
+            ],
+        });
+
+        let trace: LazyTrace<serde_json::Value> = serde_json::from_value(json).unwrap();
+
+        let issues = trace.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            vec![
+                ValidationIssue::NonContiguousIndex { state: 1, index: 2 },
+                ValidationIssue::UndeclaredVariable {
+                    state: 2,
+                    name: "y".to_string()
+                },
+                ValidationIssue::MissingParam {
+                    state: 2,
+                    name: "N".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            issues.iter().map(|i| i.state()).collect::<Vec<_>>(),
+            [1, 2, 2]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "state 2 has variable `y`, which is not declared in `vars` or `params`"
+        );
+    }
+
+    #[test]
+    fn validate_ok() {
+        let json = serde_json::json!({
+            "#meta": {},
+            "vars": ["x"],
+            "states": [{ "#meta": { "index": 0 }, "x": 1 }, { "x": 2 }],
+        });
+
+        let trace: LazyTrace<serde_json::Value> = serde_json::from_value(json).unwrap();
+        assert_eq!(trace.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_without_indices() {
+        let json = serde_json::json!({
+            "#meta": {},
+            "params": ["N"],
+            "vars": ["x"],
+            "states": [
+                { "#meta": { "index": 3 }, "N": 3, "x": 1 },
+                { "#meta": { "index": 0 }, "x": 2 },
+            ],
+        });
+
+        let trace: LazyTrace<serde_json::Value> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            TraceCheck::default().indices(false).validate_lazy(&trace),
+            Err(vec![ValidationIssue::MissingParam {
+                state: 1,
+                name: "N".to_string()
+            }])
+        );
+        assert_eq!(
+            TraceCheck::default().params(false).validate_lazy(&trace),
+            Err(vec![
+                ValidationIssue::NonContiguousIndex { state: 0, index: 3 },
+                ValidationIssue::NonContiguousIndex { state: 1, index: 0 },
+            ])
+        );
+        assert_eq!(
+            TraceCheck::default()
+                .indices(false)
+                .params(false)
+                .validate_lazy(&trace),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_decoded() {
+        // Neither `Serialize` nor a model of every variable
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct State {
+            x: i64,
+        }
+
+        let json = serde_json::json!({
+            "#meta": {},
+            "params": ["N"],
+            "vars": ["x"],
+            "states": [
+                { "#meta": { "index": 0 }, "N": 3, "x": 1 },
+                { "#meta": { "index": 2 }, "x": 2, "y": 0 },
+            ],
+        });
+
+        let trace: Trace<State> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            trace.validate(),
+            Err(vec![ValidationIssue::NonContiguousIndex {
+                state: 1,
+                index: 2
+            }])
+        );
+        assert_eq!(
+            TraceCheck::default().indices(false).validate(&trace),
+            Ok(())
+        );
+
+        let trace: LazyTrace<State> = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            trace.validate(),
+            Err(vec![
+                ValidationIssue::NonContiguousIndex { state: 1, index: 2 },
+                ValidationIssue::UndeclaredVariable {
+                    state: 1,
+                    name: "y".to_string()
+                },
+                ValidationIssue::MissingParam {
+                    state: 1,
+                    name: "N".to_string()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_vars() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct S {
+            x: i64,
+            y: i64,
+        }
+
+        let trace = |vars: &[&str]| Trace::<S> {
+            vars: vars.iter().map(|v| v.to_string()).collect(),
+            ..Trace::default()
+        };
+
+        assert_eq!(trace(&["x", "y"]).validate_vars(), Ok(()));
+        assert_eq!(trace(&["y", "x"]).validate_vars(), Ok(()));
+
+        assert_eq!(
+            trace(&["x", "y", "z"]).validate_vars(),
+            Err(VarsMismatch {
+                unmodeled: vec!["z".to_string()],
+                undeclared: vec![],
+            })
+        );
+
+        assert_eq!(
+            trace(&["x"]).validate_vars(),
+            Err(VarsMismatch {
+                unmodeled: vec![],
+                undeclared: vec!["y".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn validate_vars_flatten() {
+        #[derive(Debug, Deserialize)]
+        struct S {
+            #[serde(flatten)]
+            _vars: std::collections::HashMap<String, i64>,
+        }
+
+        let trace = Trace::<S> {
+            vars: vec!["x".to_string()],
+            ..Trace::default()
+        };
+
+        assert_eq!(trace.validate_vars(), Ok(()));
+    }
+
+    #[test]
+    fn trace_without_params_with_extra_meta() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct S {
+            x: i64,
+        }
+
+        let json = serde_json::json!({
+            "#meta": {
+                "format": "ITF",
+                "status": "ok",
+                "version": 2,
+                "producer": { "name": "apalache", "ci": true },
+            },
+            "vars": ["x"],
+            "states": [
+                { "#meta": { "index": 0 }, "x": 1 },
+                { "#meta": { "index": 1 }, "x": 2 },
+            ]
+        });
+
+        let t: Trace<S> = serde_json::from_value(json).unwrap();
+        assert_eq!(t.params, Vec::<String>::new());
+        assert_eq!(t.meta.format.as_deref(), Some("ITF"));
+        assert_eq!(t.meta.other["status"], "ok");
+        assert_eq!(t.meta.other["version"], 2);
+        assert_eq!(t.meta.version(), None);
+        assert_eq!(t.meta.other["producer"]["name"], "apalache");
+        assert_eq!(t.states.len(), 2);
+        assert_eq!(t.states[1].value, S { x: 2 });
+    }
+
+    #[test]
+    fn parse_quint() {
+        let trace = Trace::<MbtState<Counter>>::parse_with(None, quint_trace()).unwrap();
+
+        assert_eq!(trace.meta.version(), Some("0.22.1"));
+        assert_eq!(trace.meta.timestamp, Some(Timestamp::Number(1728892931714)));
+        assert_eq!(
+            serde_json::to_value(&trace.meta).unwrap()["timestamp"],
+            quint_trace()["#meta"]["timestamp"]
+        );
+        assert_eq!(trace.states.len(), 2);
+        assert_eq!(trace.states[1].value.action(), Some("step"));
+        assert_eq!(trace.states[1].value.count, ItfBigInt::from(1));
+    }
+
+    #[test]
+    fn parse_quint_without_meta() {
+        let mut json = quint_trace();
+        json.as_object_mut().unwrap().remove("#meta");
+
+        let trace = Trace::<Counter>::parse_with(Some(Format::Quint), json.clone()).unwrap();
+        assert_eq!(trace.meta, TraceMeta::default());
+
+        let err = Trace::<Counter>::parse_with(None, json).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `#meta`");
+    }
 }
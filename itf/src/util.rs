@@ -1,17 +1,603 @@
 pub mod serde {
-    pub mod display_from_str {
-        use std::{fmt::Display, str::FromStr};
+    pub mod elements {
+        use std::{fmt, marker::PhantomData};
 
         use serde::{de, Deserialize, Deserializer};
 
-        pub fn deserialize<'de, D, T, E>(deserializer: D) -> Result<T, D::Error>
+        use super::element::ElementSeed;
+        use crate::error::located;
+
+        /// Deserializes a sequence, locating errors in its elements
+        /// at `{tag}[{index}]`.
+        pub fn deserialize<'de, D, T>(
+            tag: &'static str,
+            deserializer: D,
+        ) -> Result<Vec<T>, D::Error>
         where
             D: Deserializer<'de>,
-            T: FromStr<Err = E>,
-            E: Display,
+            T: Deserialize<'de>,
+        {
+            deserializer.deserialize_seq(Elements {
+                tag,
+                marker: PhantomData,
+            })
+        }
+
+        struct Elements<T> {
+            tag: &'static str,
+            marker: PhantomData<T>,
+        }
+
+        impl<'de, T> de::Visitor<'de> for Elements<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = Vec<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+                loop {
+                    let index = elements.len();
+                    match seq.next_element_seed(ElementSeed::new()) {
+                        Ok(Some(element)) => elements.push(element),
+                        Ok(None) => return Ok(elements),
+                        Err(e) => return Err(located((self.tag, index), e)),
+                    }
+                }
+            }
+        }
+    }
+
+    pub mod element {
+        use std::marker::PhantomData;
+
+        use num_bigint::BigInt;
+        use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+        use crate::ItfBigInt;
+
+        /// Whether `T` is a plain `BigInt`, whose own serde impls encode it
+        /// as a pair of its sign and digits rather than as an ITF integer.
+        ///
+        /// `TypeId` would require `T: 'static`, which the collections do not
+        /// require of their elements, so the type is recognized by its name.
+        fn is_bigint<T: ?Sized>() -> bool {
+            std::any::type_name::<T>() == std::any::type_name::<BigInt>()
+        }
+
+        /// Deserializes an element of a `#set`, `#map`, `#tup` or sequence,
+        /// so that a plain `BigInt` element decodes from a bare integer or
+        /// a `#bigint`, like an `ItfBigInt` does.
+        ///
+        /// Any other element is decoded through its own `Deserialize` impl.
+        pub struct ElementSeed<T>(PhantomData<T>);
+
+        impl<T> ElementSeed<T> {
+            pub fn new() -> Self {
+                Self(PhantomData)
+            }
+        }
+
+        impl<'de, T> de::DeserializeSeed<'de> for ElementSeed<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = T;
+
+            fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                use serde::de::Error;
+
+                if !is_bigint::<T>() {
+                    return T::deserialize(deserializer);
+                }
+
+                let n = ItfBigInt::deserialize(deserializer)?;
+                let native = serde_json::to_value(n.value()).map_err(D::Error::custom)?;
+                T::deserialize(native).map_err(D::Error::custom)
+            }
+        }
+
+        /// Serializes an element of a `#set`, `#map` or `#tup`, so that
+        /// a plain `BigInt` element is encoded like an `ItfBigInt`,
+        /// symmetrically to [`ElementSeed`].
+        pub struct Element<'a, T: ?Sized>(pub &'a T);
+
+        impl<T> Serialize for Element<'_, T>
+        where
+            T: Serialize + ?Sized,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                use serde::ser::Error;
+
+                if !is_bigint::<T>() {
+                    return self.0.serialize(serializer);
+                }
+
+                let native = serde_json::to_value(self.0).map_err(S::Error::custom)?;
+                let n = BigInt::deserialize(native).map_err(S::Error::custom)?;
+                ItfBigInt::from(n).serialize(serializer)
+            }
+        }
+
+        /// Serializes the elements of a `#set` or `#tup` through an [`Element`].
+        pub struct Elements<I>(pub I);
+
+        impl<'a, I, T> Serialize for Elements<I>
+        where
+            I: Iterator<Item = &'a T> + Clone,
+            T: Serialize + 'a,
         {
-            let s = String::deserialize(deserializer)?;
-            FromStr::from_str(&s).map_err(de::Error::custom)
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.collect_seq(self.0.clone().map(Element))
+            }
+        }
+    }
+
+    pub mod value {
+        use std::{marker::PhantomData, slice};
+
+        use serde::{
+            de::{self, value::BorrowedStrDeserializer, IntoDeserializer, Unexpected},
+            forward_to_deserialize_any, Deserializer,
+        };
+        use serde_json::{map, Map, Number, Value};
+
+        /// Deserializes a JSON value which was buffered, eg. the variables of
+        /// a state, like `serde_json::Value` does, but reporting errors as `E`,
+        /// the error type of the deserializer it was buffered from, so that
+        /// they reach the caller as they were raised rather than reworded.
+        pub struct ValueDeserializer<'a, E> {
+            value: &'a Value,
+            marker: PhantomData<E>,
+        }
+
+        impl<'a, E> ValueDeserializer<'a, E> {
+            pub fn new(value: &'a Value) -> Self {
+                Self {
+                    value,
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<'de, E> Deserializer<'de> for ValueDeserializer<'de, E>
+        where
+            E: de::Error,
+        {
+            type Error = E;
+
+            fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.value {
+                    Value::Null => visitor.visit_unit(),
+                    Value::Bool(b) => visitor.visit_bool(*b),
+                    Value::Number(n) => visit_number(n, visitor),
+                    Value::String(s) => visitor.visit_borrowed_str(s),
+                    Value::Array(elements) => visit_array(elements, visitor),
+                    Value::Object(fields) => visit_object(fields, visitor),
+                }
+            }
+
+            fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.value {
+                    Value::Null => visitor.visit_none(),
+                    _ => visitor.visit_some(self),
+                }
+            }
+
+            fn deserialize_newtype_struct<V>(
+                self,
+                _name: &'static str,
+                visitor: V,
+            ) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                visitor.visit_newtype_struct(self)
+            }
+
+            fn deserialize_enum<V>(
+                self,
+                _name: &'static str,
+                _variants: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                match self.value {
+                    Value::String(variant) => {
+                        visitor.visit_enum(variant.as_str().into_deserializer())
+                    }
+                    Value::Object(fields) if fields.len() == 1 => {
+                        let (variant, value) = fields.iter().next().expect("one field");
+                        visitor.visit_enum(Variant {
+                            variant,
+                            value,
+                            marker: PhantomData,
+                        })
+                    }
+                    value => Err(E::invalid_type(
+                        unexpected(value),
+                        &"a string or a map with a single key",
+                    )),
+                }
+            }
+
+            fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                visitor.visit_unit()
+            }
+
+            forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+                bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+                identifier
+            }
+        }
+
+        fn visit_number<'de, V, E>(n: &Number, visitor: V) -> Result<V::Value, E>
+        where
+            V: de::Visitor<'de>,
+            E: de::Error,
+        {
+            if let Some(n) = n.as_u64() {
+                visitor.visit_u64(n)
+            } else if let Some(n) = n.as_i64() {
+                visitor.visit_i64(n)
+            } else {
+                visitor.visit_f64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+
+        fn visit_array<'de, V, E>(elements: &'de [Value], visitor: V) -> Result<V::Value, E>
+        where
+            V: de::Visitor<'de>,
+            E: de::Error,
+        {
+            let mut seq = Seq {
+                elements: elements.iter(),
+                marker: PhantomData,
+            };
+
+            let value = visitor.visit_seq(&mut seq)?;
+
+            match seq.elements.len() {
+                0 => Ok(value),
+                _ => Err(E::invalid_length(
+                    elements.len(),
+                    &"fewer elements in array",
+                )),
+            }
+        }
+
+        fn visit_object<'de, V, E>(
+            fields: &'de Map<String, Value>,
+            visitor: V,
+        ) -> Result<V::Value, E>
+        where
+            V: de::Visitor<'de>,
+            E: de::Error,
+        {
+            let mut map = Fields {
+                fields: fields.iter(),
+                value: None,
+                marker: PhantomData,
+            };
+
+            let value = visitor.visit_map(&mut map)?;
+
+            match map.fields.len() {
+                0 => Ok(value),
+                _ => Err(E::invalid_length(fields.len(), &"fewer elements in map")),
+            }
+        }
+
+        fn unexpected(value: &Value) -> Unexpected<'_> {
+            match value {
+                Value::Null => Unexpected::Unit,
+                Value::Bool(b) => Unexpected::Bool(*b),
+                Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                    (Some(n), _) => Unexpected::Unsigned(n),
+                    (_, Some(n)) => Unexpected::Signed(n),
+                    _ => Unexpected::Float(n.as_f64().unwrap_or(f64::NAN)),
+                },
+                Value::String(s) => Unexpected::Str(s),
+                Value::Array(_) => Unexpected::Seq,
+                Value::Object(_) => Unexpected::Map,
+            }
+        }
+
+        struct Seq<'a, E> {
+            elements: slice::Iter<'a, Value>,
+            marker: PhantomData<E>,
+        }
+
+        impl<'de, E> de::SeqAccess<'de> for Seq<'de, E>
+        where
+            E: de::Error,
+        {
+            type Error = E;
+
+            fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, E>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                match self.elements.next() {
+                    Some(element) => seed.deserialize(ValueDeserializer::new(element)).map(Some),
+                    None => Ok(None),
+                }
+            }
+
+            fn size_hint(&self) -> Option<usize> {
+                Some(self.elements.len())
+            }
+        }
+
+        struct Fields<'a, E> {
+            fields: map::Iter<'a>,
+            value: Option<&'a Value>,
+            marker: PhantomData<E>,
+        }
+
+        impl<'de, E> de::MapAccess<'de> for Fields<'de, E>
+        where
+            E: de::Error,
+        {
+            type Error = E;
+
+            fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, E>
+            where
+                K: de::DeserializeSeed<'de>,
+            {
+                match self.fields.next() {
+                    Some((key, value)) => {
+                        self.value = Some(value);
+                        seed.deserialize(BorrowedStrDeserializer::new(key))
+                            .map(Some)
+                    }
+                    None => Ok(None),
+                }
+            }
+
+            fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, E>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                match self.value.take() {
+                    Some(value) => seed.deserialize(ValueDeserializer::new(value)),
+                    None => Err(E::custom("value is missing")),
+                }
+            }
+
+            fn size_hint(&self) -> Option<usize> {
+                Some(self.fields.len())
+            }
+        }
+
+        /// The variant of an enum, encoded as a map with a single key.
+        struct Variant<'a, E> {
+            variant: &'a str,
+            value: &'a Value,
+            marker: PhantomData<E>,
+        }
+
+        impl<'de, E> de::EnumAccess<'de> for Variant<'de, E>
+        where
+            E: de::Error,
+        {
+            type Error = E;
+            type Variant = ValueDeserializer<'de, E>;
+
+            fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self::Variant), E>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variant))?;
+                Ok((variant, ValueDeserializer::new(self.value)))
+            }
+        }
+
+        impl<'de, E> de::VariantAccess<'de> for ValueDeserializer<'de, E>
+        where
+            E: de::Error,
+        {
+            type Error = E;
+
+            fn unit_variant(self) -> Result<(), E> {
+                de::Deserialize::deserialize(self)
+            }
+
+            fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, E>
+            where
+                T: de::DeserializeSeed<'de>,
+            {
+                seed.deserialize(self)
+            }
+
+            fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.deserialize_seq(visitor)
+            }
+
+            fn struct_variant<V>(
+                self,
+                _fields: &'static [&'static str],
+                visitor: V,
+            ) -> Result<V::Value, E>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.deserialize_map(visitor)
+            }
+        }
+    }
+
+    pub mod struct_fields {
+        use serde::{de, forward_to_deserialize_any, Deserialize, Deserializer};
+
+        /// Returns the field names a type asks for when deserialized as a struct,
+        /// or `None` if it does not deserialize as a plain struct (eg. because
+        /// it uses `#[serde(flatten)]` or has a hand-written `Deserialize` impl).
+        pub fn of<'de, T>() -> Option<&'static [&'static str]>
+        where
+            T: Deserialize<'de>,
+        {
+            let mut fields = None;
+            let _ = T::deserialize(Introspect(&mut fields));
+            fields
+        }
+
+        struct Introspect<'a>(&'a mut Option<&'static [&'static str]>);
+
+        impl<'de, 'a> Deserializer<'de> for Introspect<'a> {
+            type Error = de::value::Error;
+
+            fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                Err(de::Error::custom("not a struct"))
+            }
+
+            fn deserialize_struct<V>(
+                self,
+                _name: &'static str,
+                fields: &'static [&'static str],
+                _visitor: V,
+            ) -> Result<V::Value, Self::Error>
+            where
+                V: de::Visitor<'de>,
+            {
+                *self.0 = Some(fields);
+                Err(de::Error::custom("not a struct"))
+            }
+
+            forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+                bytes byte_buf option unit unit_struct newtype_struct seq tuple
+                tuple_struct map enum identifier ignored_any
+            }
+        }
+    }
+}
+
+pub mod derive {
+    use std::fmt;
+
+    use serde::{de, de::DeserializeOwned, forward_to_deserialize_any, Deserialize, Deserializer};
+    use serde_json::{Map, Value};
+
+    use super::serde::value::ValueDeserializer;
+    use crate::error::located;
+
+    /// The variables of a state, as used by the code generated by `#[derive(ItfState)]`.
+    pub struct Vars(Map<String, Value>);
+
+    impl Vars {
+        /// Deserializes the variables as a struct with the given fields, so that
+        /// `struct_fields::of` knows the variables of the derived state type.
+        pub fn deserialize_struct<'de, D>(
+            deserializer: D,
+            name: &'static str,
+            fields: &'static [&'static str],
+        ) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct VarsVisitor;
+
+            impl<'de> de::Visitor<'de> for VarsVisitor {
+                type Value = Map<String, Value>;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("the variables of a state")
+                }
+
+                fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+                where
+                    A: de::MapAccess<'de>,
+                {
+                    Map::deserialize(de::value::MapAccessDeserializer::new(map))
+                }
+            }
+
+            deserializer
+                .deserialize_struct(name, fields, VarsVisitor)
+                .map(Self)
+        }
+
+        pub fn take<T, E>(&mut self, name: &'static str) -> Result<T, E>
+        where
+            T: DeserializeOwned,
+            E: de::Error,
+        {
+            match self.0.remove(name) {
+                Some(value) => T::deserialize(ValueDeserializer::<E>::new(&value))
+                    .map_err(|e| located(name, e)),
+                // Like serde's derive, let eg. `Option` fields be absent
+                None => T::deserialize(Absent).map_err(|_| E::missing_field(name)),
+            }
+        }
+
+        pub fn take_or_default<T, E>(&mut self, name: &'static str) -> Result<T, E>
+        where
+            T: DeserializeOwned + Default,
+            E: de::Error,
+        {
+            match self.0.remove(name) {
+                Some(value) => T::deserialize(ValueDeserializer::<E>::new(&value))
+                    .map_err(|e| located(name, e)),
+                None => Ok(T::default()),
+            }
+        }
+    }
+
+    /// The value of an absent variable, which is `None` to every visitor, unlike
+    /// a `null` which is `()` to `deserialize_any`, so that eg. `ItfOption` can
+    /// tell them apart.
+    struct Absent;
+
+    impl<'de> Deserializer<'de> for Absent {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            visitor.visit_none()
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
         }
     }
 }
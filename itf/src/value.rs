@@ -0,0 +1,613 @@
+use std::{cmp::Ordering, collections::BTreeMap, fmt, mem::take};
+
+use num_bigint::BigInt;
+use serde::{de, ser::SerializeStruct, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{located, raise},
+    util::serde::value::ValueDeserializer,
+    ItfBigInt, ItfError,
+};
+
+/// An ITF value of any type, for inspecting traces without a Rust type
+/// for their states.
+///
+/// Its `Display` impl prints the value in TLA+ syntax.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ItfValue {
+    /// An integer which fits in an `i64`
+    Int(i64),
+
+    /// An integer which does not fit in an `i64`
+    BigInt(BigInt),
+
+    Bool(bool),
+
+    Str(String),
+
+    /// The elements of a `#set`, in the order they appear in the trace
+    Set(Vec<ItfValue>),
+
+    /// The entries of a `#map`, in the order they appear in the trace
+    Map(Vec<(ItfValue, ItfValue)>),
+
+    /// The elements of a `#tup`
+    Tuple(Vec<ItfValue>),
+
+    /// The elements of a JSON array, ie. a TLA+ sequence
+    List(Vec<ItfValue>),
+
+    Record(BTreeMap<String, ItfValue>),
+
+    /// The string representation of an `#unserializable` value
+    Unserializable(String),
+}
+
+impl ItfValue {
+    fn from_json<E>(value: Value) -> Result<Self, E>
+    where
+        E: de::Error,
+    {
+        let elements = |tag: &str, elements: Vec<Value>| {
+            elements
+                .into_iter()
+                .enumerate()
+                .map(|(i, e)| Self::from_json::<E>(e).map_err(|e| located((tag, i), e)))
+                .collect::<Result<Vec<_>, E>>()
+        };
+
+        match value {
+            Value::Null => Err(E::invalid_type(de::Unexpected::Unit, &"an ITF value")),
+            Value::Bool(b) => Ok(Self::Bool(b)),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(n), _) => Ok(Self::Int(n)),
+                (None, Some(n)) => Ok(Self::BigInt(BigInt::from(n))),
+                (None, None) => Err(E::invalid_type(
+                    de::Unexpected::Float(n.as_f64().unwrap_or_default()),
+                    &"an integer",
+                )),
+            },
+            Value::String(s) => Ok(Self::Str(s)),
+            Value::Array(elems) => elements("", elems).map(Self::List),
+            Value::Object(mut obj) => {
+                let tag = match obj.keys().next() {
+                    Some(key) if obj.len() == 1 && key.starts_with('#') => key.clone(),
+                    _ => {
+                        return obj
+                            .into_iter()
+                            .map(|(k, v)| match Self::from_json::<E>(v) {
+                                Ok(v) => Ok((k, v)),
+                                Err(e) => Err(located(k.as_str(), e)),
+                            })
+                            .collect::<Result<_, E>>()
+                            .map(Self::Record);
+                    }
+                };
+
+                let value = obj.remove(&tag).unwrap_or_default();
+
+                let array = |value: Value| match value {
+                    Value::Array(elems) => Ok(elems),
+                    _ => Err(located(tag.as_str(), "expected an array")),
+                };
+
+                match tag.as_str() {
+                    "#bigint" => {
+                        let n =
+                            ItfBigInt::deserialize(ValueDeserializer::<E>::new(&value))?.value();
+                        Ok(match i64::try_from(&n) {
+                            Ok(n) => Self::Int(n),
+                            Err(_) => Self::BigInt(n),
+                        })
+                    }
+                    "#set" => elements("#set", array(value)?).map(Self::Set),
+                    "#tup" => elements("#tup", array(value)?).map(Self::Tuple),
+                    "#map" => array(value)?
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, entry)| match entry {
+                            Value::Array(mut entry) if entry.len() == 2 => {
+                                let key = Self::from_json::<E>(take(&mut entry[0]));
+                                let value = Self::from_json::<E>(take(&mut entry[1]));
+                                Ok((
+                                    key.map_err(|e| located(("#map", i, 0), e))?,
+                                    value.map_err(|e| located(("#map", i, 1), e))?,
+                                ))
+                            }
+                            _ => Err(located(("#map", i), "expected a key-value pair")),
+                        })
+                        .collect::<Result<_, E>>()
+                        .map(Self::Map),
+                    "#unserializable" => match value {
+                        Value::String(s) => Ok(Self::Unserializable(s)),
+                        _ => Err(located(tag.as_str(), "expected a string")),
+                    },
+                    _ => Err(raise(ItfError::UnknownTag(tag))),
+                }
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ItfValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Self::from_json(Value::deserialize(deserializer)?)
+    }
+}
+
+impl Serialize for ItfValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            ItfValue::Int(n) => n.serialize(serializer),
+            ItfValue::BigInt(n) => ItfBigInt::from(n.clone()).serialize(serializer),
+            ItfValue::Bool(b) => b.serialize(serializer),
+            ItfValue::Str(s) => s.serialize(serializer),
+            ItfValue::Set(elems) => Tagged("#set", elems).serialize(serializer),
+            ItfValue::Map(entries) => Tagged("#map", entries).serialize(serializer),
+            ItfValue::Tuple(elems) => Tagged("#tup", elems).serialize(serializer),
+            ItfValue::List(elems) => elems.serialize(serializer),
+            ItfValue::Record(fields) => fields.serialize(serializer),
+            ItfValue::Unserializable(s) => Tagged("#unserializable", s).serialize(serializer),
+        }
+    }
+}
+
+/// Serializes a value as an object with a single ITF tag.
+struct Tagged<'a, T>(&'static str, &'a T);
+
+impl<T> Serialize for Tagged<'_, T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("Tagged", 1)?;
+        s.serialize_field(self.0, self.1)?;
+        s.end()
+    }
+}
+
+impl ItfValue {
+    /// Converts any value which serializes to ITF, such as a state, into an `ItfValue`.
+    pub fn from_itf<T>(value: &T) -> Result<Self, serde_json::Error>
+    where
+        T: Serialize,
+    {
+        Self::from_json(serde_json::to_value(value)?)
+    }
+}
+
+/// Displays a value which serializes to ITF, such as a state, in TLA+ syntax.
+///
+/// Values which do not serialize to ITF, eg. a `None` or a float, are displayed
+/// as `<unrenderable: ..>`, along with the reason. Use [`print_state`] to get
+/// an error for them instead.
+///
+/// ```
+/// use itf::{ItfSet, Tla};
+///
+/// let set: ItfSet<i64> = [3, 1, 2].into_iter().collect();
+/// assert_eq!(Tla(&set).to_string(), "{1, 2, 3}");
+/// ```
+pub struct Tla<'a, T>(pub &'a T);
+
+impl<T> fmt::Display for Tla<'_, T>
+where
+    T: Serialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ItfValue::from_itf(self.0) {
+            Ok(value) => write!(f, "{value}"),
+            Err(e) => write!(f, "<unrenderable: {e}>"),
+        }
+    }
+}
+
+/// How [`print_state`] renders a state.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Rendering {
+    /// Pretty-printed ITF JSON, as found in a trace
+    #[default]
+    Json,
+
+    /// TLA+ syntax, as displayed by [`Tla`]
+    Tla,
+}
+
+/// Renders a value which serializes to ITF, such as a state, eg. to compare it
+/// side by side with the spec.
+///
+/// The rendering is deterministic: record fields are sorted by name, and the
+/// elements of sets and the entries of maps by value,
+/// whatever their order in the trace or in the collection they were decoded into.
+///
+/// ```
+/// use itf::{print_state, ItfSet, Rendering};
+///
+/// let set: ItfSet<i64> = [3, 1, 2].into_iter().collect();
+///
+/// assert_eq!(print_state(&set, Rendering::Tla)?, "{1, 2, 3}");
+/// assert_eq!(
+///     print_state(&set, Rendering::Json)?,
+///     "{\n  \"#set\": [\n    1,\n    2,\n    3\n  ]\n}"
+/// );
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+pub fn print_state<T>(state: &T, rendering: Rendering) -> Result<String, serde_json::Error>
+where
+    T: Serialize,
+{
+    let value = ItfValue::from_itf(state)?;
+
+    match rendering {
+        Rendering::Json => serde_json::to_string_pretty(&value.sorted()),
+        Rendering::Tla => Ok(value.to_string()),
+    }
+}
+
+impl ItfValue {
+    /// The position of the kind of the value in the order between values of different kinds.
+    fn rank(&self) -> u8 {
+        match self {
+            ItfValue::Int(_) | ItfValue::BigInt(_) => 0,
+            ItfValue::Bool(_) => 1,
+            ItfValue::Str(_) => 2,
+            ItfValue::Set(_) => 3,
+            ItfValue::Map(_) => 4,
+            ItfValue::Tuple(_) => 5,
+            ItfValue::List(_) => 6,
+            ItfValue::Record(_) => 7,
+            ItfValue::Unserializable(_) => 8,
+        }
+    }
+}
+
+/// Values are ordered by kind first, then integers numerically, whether they
+/// fit in an `i64` or not, and the other values by their contents.
+impl Ord for ItfValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ItfValue::Int(a), ItfValue::Int(b)) => a.cmp(b),
+            (ItfValue::BigInt(a), ItfValue::BigInt(b)) => a.cmp(b),
+            // an `Int` sorts before a `BigInt` of the same value, to stay consistent with `Eq`
+            (ItfValue::Int(a), ItfValue::BigInt(b)) => BigInt::from(*a).cmp(b).then(Ordering::Less),
+            (ItfValue::BigInt(a), ItfValue::Int(b)) => {
+                a.cmp(&BigInt::from(*b)).then(Ordering::Greater)
+            }
+            (ItfValue::Bool(a), ItfValue::Bool(b)) => a.cmp(b),
+            (ItfValue::Str(a), ItfValue::Str(b)) => a.cmp(b),
+            (ItfValue::Set(a), ItfValue::Set(b)) => a.cmp(b),
+            (ItfValue::Map(a), ItfValue::Map(b)) => a.cmp(b),
+            (ItfValue::Tuple(a), ItfValue::Tuple(b)) => a.cmp(b),
+            (ItfValue::List(a), ItfValue::List(b)) => a.cmp(b),
+            (ItfValue::Record(a), ItfValue::Record(b)) => a.cmp(b),
+            (ItfValue::Unserializable(a), ItfValue::Unserializable(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for ItfValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ItfValue {
+    /// Sorts the elements of sets and the entries of maps, recursively,
+    /// by value, as `Display` does.
+    fn sorted(self) -> Self {
+        let sorted = |elems: Vec<ItfValue>| elems.into_iter().map(Self::sorted).collect();
+
+        match self {
+            ItfValue::Set(elems) => {
+                let mut elems: Vec<_> = sorted(elems);
+                elems.sort();
+                ItfValue::Set(elems)
+            }
+            ItfValue::Map(entries) => {
+                let mut entries = entries
+                    .into_iter()
+                    .map(|(k, v)| (k.sorted(), v.sorted()))
+                    .collect::<Vec<_>>();
+                entries.sort();
+                ItfValue::Map(entries)
+            }
+            ItfValue::Tuple(elems) => ItfValue::Tuple(sorted(elems)),
+            ItfValue::List(elems) => ItfValue::List(sorted(elems)),
+            ItfValue::Record(fields) => {
+                ItfValue::Record(fields.into_iter().map(|(k, v)| (k, v.sorted())).collect())
+            }
+            value => value,
+        }
+    }
+}
+
+/// Writes the given items separated by `sep`.
+fn write_all<I>(f: &mut fmt::Formatter<'_>, items: I, sep: &str) -> fmt::Result
+where
+    I: IntoIterator,
+    I::Item: fmt::Display,
+{
+    let items = items.into_iter().map(|i| i.to_string()).collect::<Vec<_>>();
+
+    f.write_str(&items.join(sep))
+}
+
+impl fmt::Display for ItfValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItfValue::Int(n) => write!(f, "{n}"),
+            ItfValue::BigInt(n) => write!(f, "{n}"),
+            ItfValue::Bool(true) => f.write_str("TRUE"),
+            ItfValue::Bool(false) => f.write_str("FALSE"),
+            ItfValue::Str(s) => write!(f, "{s:?}"),
+            ItfValue::Set(elems) => {
+                // sorted by value, so that sets display the same whatever their order in the trace
+                let mut elems = elems.iter().cloned().map(Self::sorted).collect::<Vec<_>>();
+                elems.sort();
+
+                f.write_str("{")?;
+                write_all(f, elems, ", ")?;
+                f.write_str("}")
+            }
+            ItfValue::Map(entries) if entries.is_empty() => f.write_str("SetAsFun({})"),
+            ItfValue::Map(entries) => {
+                let mut entries = entries
+                    .iter()
+                    .map(|(k, v)| (k.clone().sorted(), v.clone().sorted()))
+                    .collect::<Vec<_>>();
+                entries.sort();
+                let entries = entries.iter().map(|(k, v)| format!("{k} :> {v}"));
+
+                f.write_str("(")?;
+                write_all(f, entries, " @@ ")?;
+                f.write_str(")")
+            }
+            ItfValue::Tuple(elems) | ItfValue::List(elems) => {
+                f.write_str("<<")?;
+                write_all(f, elems, ", ")?;
+                f.write_str(">>")
+            }
+            ItfValue::Record(fields) => {
+                let fields = fields.iter().map(|(k, v)| format!("{k} |-> {v}"));
+
+                f.write_str("[")?;
+                write_all(f, fields, ", ")?;
+                f.write_str("]")
+            }
+            ItfValue::Unserializable(s) => f.write_str(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::State;
+
+    #[test]
+    fn deserialize() {
+        let json = json!({
+            "n": 1,
+            "big": { "#bigint": "1234567891011121314151617181920" },
+            "small": { "#bigint": "-3" },
+            "s": { "#set": [true, false] },
+            "m": { "#map": [[{ "#tup": [1, "a"] }, [1, 2]]] },
+            "u": { "#unserializable": "Int" },
+        });
+
+        let value: ItfValue = serde_json::from_value(json).unwrap();
+
+        let ItfValue::Record(fields) = value else {
+            panic!("expected a record, got {value:?}");
+        };
+
+        assert_eq!(fields["n"], ItfValue::Int(1));
+        assert_eq!(
+            fields["big"],
+            ItfValue::BigInt("1234567891011121314151617181920".parse().unwrap())
+        );
+        assert_eq!(fields["small"], ItfValue::Int(-3));
+        assert_eq!(
+            fields["s"],
+            ItfValue::Set(vec![ItfValue::Bool(true), ItfValue::Bool(false)])
+        );
+        assert_eq!(
+            fields["m"],
+            ItfValue::Map(vec![(
+                ItfValue::Tuple(vec![ItfValue::Int(1), ItfValue::Str("a".to_string())]),
+                ItfValue::List(vec![ItfValue::Int(1), ItfValue::Int(2)]),
+            )])
+        );
+        assert_eq!(fields["u"], ItfValue::Unserializable("Int".to_string()));
+    }
+
+    #[test]
+    fn deserialize_unknown_tag() {
+        let err = crate::from_value::<ItfValue>(&json!({ "x": { "#foo": 1 } })).unwrap_err();
+
+        assert_eq!(err.path(), Some("x"));
+        assert!(matches!(err.inner(), ItfError::UnknownTag(tag) if tag == "#foo"));
+    }
+
+    #[test]
+    fn deserialize_state() {
+        let json = json!({ "#meta": { "index": 0 }, "x": 1 });
+        let state: State<BTreeMap<String, ItfValue>> = serde_json::from_value(json).unwrap();
+
+        assert_eq!(state.meta.index, Some(0));
+        assert_eq!(state.value["x"], ItfValue::Int(1));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let json = json!({
+            "big": { "#bigint": "1234567891011121314151617181920" },
+            "m": { "#map": [[{ "#tup": [1, "a"] }, { "#set": [[1, 2]] }]] },
+            "u": { "#unserializable": "Int" },
+        });
+
+        let value: ItfValue = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&value).unwrap(), json);
+    }
+
+    #[test]
+    fn display_is_deterministic() {
+        let a: ItfValue = serde_json::from_value(json!({
+            "#map": [["b", { "#set": [2, 1] }], ["a", { "#set": [] }]]
+        }))
+        .unwrap();
+
+        let b: ItfValue = serde_json::from_value(json!({
+            "#map": [["a", { "#set": [] }], ["b", { "#set": [1, 2] }]]
+        }))
+        .unwrap();
+
+        assert_eq!(a.to_string(), r#"("a" :> {} @@ "b" :> {1, 2})"#);
+        assert_eq!(a.to_string(), b.to_string());
+    }
+
+    #[test]
+    fn display_sorts_by_value() {
+        let value: ItfValue = serde_json::from_value(json!({
+            "#set": [1, 2, 10, -3, { "#bigint": "-100000000000000000000" }]
+        }))
+        .unwrap();
+
+        assert_eq!(value.to_string(), "{-100000000000000000000, -3, 1, 2, 10}");
+
+        let a: ItfValue = serde_json::from_value(json!({
+            "#set": [{ "#set": [10, 2] }, { "#set": [1, 3] }]
+        }))
+        .unwrap();
+
+        let b: ItfValue = serde_json::from_value(json!({
+            "#set": [{ "#set": [3, 1] }, { "#set": [2, 10] }]
+        }))
+        .unwrap();
+
+        assert_eq!(a.to_string(), "{{1, 3}, {2, 10}}");
+        assert_eq!(a.to_string(), b.to_string());
+    }
+
+    #[test]
+    fn ord() {
+        let big = ItfValue::BigInt(BigInt::from(i64::MAX) + 1);
+
+        assert!(ItfValue::Int(-3) < ItfValue::Int(2));
+        assert!(ItfValue::Int(i64::MAX) < big);
+        assert!(ItfValue::Int(5) < ItfValue::BigInt(5.into()));
+        assert!(big < ItfValue::Bool(false));
+        assert!(ItfValue::Str("b".to_string()) > ItfValue::Str("a".to_string()));
+    }
+
+    #[test]
+    fn display_state() {
+        use crate::{Itf, ItfBigInt, ItfMap, ItfSet, ItfTuple};
+
+        #[derive(Serialize)]
+        struct State {
+            balances: ItfMap<String, ItfBigInt>,
+            owners: ItfSet<ItfTuple<(i64, String)>>,
+        }
+
+        let state = State {
+            balances: (0..10)
+                .map(|i| (format!("user{i}"), ItfBigInt::from(i)))
+                .collect(),
+            owners: std::iter::once(Itf::new((1, "alice".to_string()))).collect(),
+        };
+
+        assert_eq!(
+            Tla(&state).to_string(),
+            r#"[balances |-> ("user0" :> 0 @@ "user1" :> 1 @@ "user2" :> 2 @@ "user3" :> 3 @@ "user4" :> 4 @@ "user5" :> 5 @@ "user6" :> 6 @@ "user7" :> 7 @@ "user8" :> 8 @@ "user9" :> 9), owners |-> {<<1, "alice">>}]"#
+        );
+    }
+
+    #[test]
+    fn display_unrenderable() {
+        #[derive(Serialize)]
+        struct State {
+            x: Option<i64>,
+        }
+
+        let state = State { x: None };
+        assert_eq!(
+            Tla(&state).to_string(),
+            "<unrenderable: at `x`: invalid type: null, expected an ITF value>"
+        );
+        assert!(print_state(&state, Rendering::Tla).is_err());
+
+        assert_eq!(
+            Tla(&1.5).to_string(),
+            "<unrenderable: invalid type: floating point `1.5`, expected an integer>"
+        );
+        assert!(print_state(&1.5, Rendering::Json).is_err());
+    }
+
+    #[test]
+    fn print_state_is_deterministic() {
+        use crate::{ItfMap, ItfSet};
+
+        #[derive(Serialize)]
+        struct State {
+            owners: ItfMap<String, ItfSet<i64>>,
+            count: i64,
+        }
+
+        let state = State {
+            owners: (0..5)
+                .rev()
+                .map(|i| (format!("user{i}"), (0..i).rev().collect()))
+                .collect(),
+            count: 5,
+        };
+
+        let json = print_state(&state, Rendering::Json).unwrap();
+        assert!(json.starts_with("{\n  \"count\": 5,\n  \"owners\": {"));
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["owners"]["#map"][0], json!(["user0", { "#set": [] }]));
+        assert_eq!(
+            value["owners"]["#map"][4],
+            json!(["user4", { "#set": [0, 1, 2, 3] }])
+        );
+
+        assert_eq!(
+            print_state(&state, Rendering::Tla).unwrap(),
+            Tla(&state).to_string()
+        );
+    }
+
+    #[test]
+    fn display() {
+        let json = json!({
+            "b": true,
+            "m": { "#map": [["alice", { "#set": [1, 2] }], ["bob", { "#set": [] }]] },
+            "e": { "#map": [] },
+            "t": { "#tup": [1, { "#bigint": "1234567891011121314151617181920" }] },
+            "l": [],
+            "u": { "#unserializable": "Int" },
+        });
+
+        let value: ItfValue = serde_json::from_value(json).unwrap();
+
+        assert_eq!(
+            value.to_string(),
+            r#"[b |-> TRUE, e |-> SetAsFun({}), l |-> <<>>, m |-> ("alice" :> {1, 2} @@ "bob" :> {}), t |-> <<1, 1234567891011121314151617181920>>, u |-> Int]"#
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use num_bigint::BigInt;
 use serde::Deserialize;
 
-use itf::{trace_from_str, Itf, ItfMap, ItfSet};
+use itf::{trace_from_str, Itf, ItfBigInt, ItfMap, ItfSet, TraceMeta};
 
 #[test]
 fn cannibals() {
@@ -41,6 +41,12 @@ fn cannibals() {
     let data = include_str!("../tests/fixtures/MissionariesAndCannibals.itf.json");
     let trace = trace_from_str::<State>(data).unwrap();
 
+    assert_eq!(
+        trace.meta.source.as_deref(),
+        Some("MC_MissionariesAndCannibalsTyped.tla")
+    );
+    assert_eq!(trace.meta.description, None);
+
     dbg!(trace);
 }
 
@@ -95,5 +101,67 @@ fn insufficent_success_9() {
     let data = include_str!("../tests/fixtures/TestInsufficientSuccess9.itf.json");
     let trace = trace_from_str::<State>(data).unwrap();
 
+    assert_eq!(trace.meta.source, None);
+    assert_eq!(
+        trace.meta.description.as_deref(),
+        Some("Created by Apalache on Sat Sep 24 20:45:34 CEST 2022")
+    );
+    assert!(trace.meta.other.is_empty());
+    assert!(trace.validate_vars().is_ok());
+
     dbg!(trace);
 }
+
+#[test]
+fn int_var_bare_and_bigint() {
+    #[derive(Clone, Debug, Deserialize)]
+    struct State {
+        supply: ItfBigInt,
+    }
+
+    let data = r##"{
+        "#meta": {},
+        "vars": ["supply"],
+        "states": [
+            { "supply": 1 },
+            { "supply": { "#bigint": "9223372036854775808" } },
+            { "supply": -2 }
+        ]
+    }"##;
+
+    let supplies = trace_from_str::<State>(data)
+        .unwrap()
+        .into_states()
+        .into_iter()
+        .map(|state| state.supply.value())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        supplies,
+        [
+            BigInt::from(1),
+            BigInt::from(i64::MAX) + 1,
+            BigInt::from(-2)
+        ]
+    );
+}
+
+#[test]
+fn meta_roundtrip() {
+    let fixtures = [
+        include_str!("../tests/fixtures/MissionariesAndCannibals.itf.json"),
+        include_str!("../tests/fixtures/TestInsufficientSuccess9.itf.json"),
+    ];
+
+    for data in fixtures {
+        let json: serde_json::Value = serde_json::from_str(data).unwrap();
+
+        let meta: TraceMeta = serde_json::from_value(json["#meta"].clone()).unwrap();
+        let encoded = serde_json::to_value(&meta).unwrap();
+        assert_eq!(encoded, json["#meta"]);
+
+        let decoded: TraceMeta = serde_json::from_value(encoded.clone()).unwrap();
+        assert_eq!(decoded, meta);
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), encoded);
+    }
+}
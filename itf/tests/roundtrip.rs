@@ -0,0 +1,308 @@
+//! Property tests checking that serializing then deserializing an ITF value
+//! yields the original value, over randomly generated values.
+//!
+//! The values are generated by the small seeded generator below, along the
+//! lines of `proptest`'s `Arbitrary`, which is not a dependency of the crate.
+
+use std::fmt::Debug;
+
+use num_bigint::BigInt;
+use serde::{de::DeserializeOwned, Serialize};
+
+use itf::{Itf, ItfBTreeMap, ItfBTreeSet, ItfBigInt, ItfMap, ItfSet, ItfTuple, ItfValue};
+
+const CASES: usize = 256;
+
+/// A xorshift generator, so that failures are reproducible from the seed.
+struct Gen(u64);
+
+impl Gen {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn len(&mut self) -> usize {
+        self.below(6) as usize
+    }
+}
+
+trait Arbitrary: Sized {
+    fn arbitrary(g: &mut Gen) -> Self;
+}
+
+impl Arbitrary for i64 {
+    fn arbitrary(g: &mut Gen) -> Self {
+        match g.below(4) {
+            0 => [i64::MIN, -1, 0, 1, i64::MAX][g.below(5) as usize],
+            1 => g.below(10) as i64 - 5,
+            _ => g.next() as i64,
+        }
+    }
+}
+
+impl Arbitrary for bool {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.below(2) == 0
+    }
+}
+
+impl Arbitrary for String {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = g.len();
+        (0..len)
+            .map(|_| ['a', 'b', 'c', '#', '"', '\\', 'é'][g.below(7) as usize])
+            .collect()
+    }
+}
+
+impl Arbitrary for BigInt {
+    fn arbitrary(g: &mut Gen) -> Self {
+        // Favour values around the bounds of `i64`, where encodings switch
+        match g.below(4) {
+            0 => BigInt::from(i64::MIN) + (g.below(5) as i64 - 2),
+            1 => BigInt::from(i64::MAX) + (g.below(5) as i64 - 2),
+            2 => BigInt::from(i64::arbitrary(g)),
+            _ => BigInt::from(i64::arbitrary(g)) * BigInt::from(u64::MAX) * g.below(1000),
+        }
+    }
+}
+
+impl Arbitrary for ItfBigInt {
+    fn arbitrary(g: &mut Gen) -> Self {
+        ItfBigInt::from(BigInt::arbitrary(g))
+    }
+}
+
+impl<A, B> Arbitrary for ItfTuple<(A, B)>
+where
+    A: Arbitrary,
+    B: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Itf::new((A::arbitrary(g), B::arbitrary(g)))
+    }
+}
+
+impl<T> Arbitrary for ItfSet<T>
+where
+    T: Arbitrary + Eq + std::hash::Hash,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = g.len();
+        (0..len).map(|_| T::arbitrary(g)).collect()
+    }
+}
+
+impl<T> Arbitrary for ItfBTreeSet<T>
+where
+    T: Arbitrary + Ord,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = g.len();
+        (0..len).map(|_| T::arbitrary(g)).collect()
+    }
+}
+
+impl<K, V> Arbitrary for ItfMap<K, V>
+where
+    K: Arbitrary + Eq + std::hash::Hash,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = g.len();
+        (0..len)
+            .map(|_| (K::arbitrary(g), V::arbitrary(g)))
+            .collect()
+    }
+}
+
+impl<K, V> Arbitrary for ItfBTreeMap<K, V>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = g.len();
+        (0..len)
+            .map(|_| (K::arbitrary(g), V::arbitrary(g)))
+            .collect()
+    }
+}
+
+/// The depth of the generated `ItfValue`s, so that they stay small.
+const VALUE_DEPTH: u32 = 3;
+
+impl Arbitrary for ItfValue {
+    fn arbitrary(g: &mut Gen) -> Self {
+        arbitrary_value(g, VALUE_DEPTH)
+    }
+}
+
+/// Generates an `ItfValue` nested at most `depth` levels deep, in the canonical
+/// form it is decoded into, eg. a `#bigint` which fits in an `i64` is an `Int`.
+fn arbitrary_value(g: &mut Gen, depth: u32) -> ItfValue {
+    let elems = |g: &mut Gen| {
+        let len = g.len();
+        (0..len)
+            .map(|_| arbitrary_value(g, depth - 1))
+            .collect::<Vec<_>>()
+    };
+
+    let kinds = if depth == 0 { 5 } else { 10 };
+
+    match g.below(kinds) {
+        0 => ItfValue::Int(i64::arbitrary(g)),
+        1 => {
+            let n = BigInt::from(i64::MAX) + 1 + BigInt::from(g.next()) * g.below(1000);
+            ItfValue::BigInt(if bool::arbitrary(g) { n } else { -n - 1 })
+        }
+        2 => ItfValue::Bool(bool::arbitrary(g)),
+        3 => ItfValue::Str(String::arbitrary(g)),
+        4 => ItfValue::Unserializable(["Int", "Nat", "STRING"][g.below(3) as usize].to_string()),
+        5 => ItfValue::Set(elems(g)),
+        6 => ItfValue::Tuple(elems(g)),
+        7 => ItfValue::List(elems(g)),
+        8 => {
+            let len = g.len();
+            let entries = (0..len)
+                .map(|_| (arbitrary_value(g, depth - 1), arbitrary_value(g, depth - 1)))
+                .collect();
+            ItfValue::Map(entries)
+        }
+        _ => {
+            // Field names never start with `#`, which would make a tag of them
+            let len = g.len();
+            let fields = (0..len)
+                .map(|_| {
+                    let name = ["x", "y", "balances", "é"][g.below(4) as usize].to_string();
+                    (name, arbitrary_value(g, depth - 1))
+                })
+                .collect();
+            ItfValue::Record(fields)
+        }
+    }
+}
+
+fn check_roundtrip<T>()
+where
+    T: Arbitrary + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    for seed in 1..=CASES as u64 {
+        let mut g = Gen(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let value = T::arbitrary(&mut g);
+
+        let json = serde_json::to_value(&value).unwrap();
+        let decoded: T = serde_json::from_value(json.clone())
+            .unwrap_or_else(|e| panic!("seed {seed}: failed to decode {json}: {e}"));
+
+        assert_eq!(decoded, value, "seed {seed}: {json}");
+    }
+}
+
+/// Checks that the values of `T` are encoded like the values of `U`
+/// generated from the same seed, eg. plain `BigInt` elements like `ItfBigInt` ones.
+fn check_same_encoding<T, U>()
+where
+    T: Arbitrary + Serialize,
+    U: Arbitrary + DeserializeOwned + PartialEq + Debug,
+{
+    for seed in 1..=CASES as u64 {
+        let seed = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let value = T::arbitrary(&mut Gen(seed));
+        let expected = U::arbitrary(&mut Gen(seed));
+
+        let json = serde_json::to_value(&value).unwrap();
+        let decoded: U = serde_json::from_value(json.clone())
+            .unwrap_or_else(|e| panic!("seed {seed}: failed to decode {json}: {e}"));
+
+        assert_eq!(decoded, expected, "seed {seed}: {json}");
+    }
+}
+
+#[test]
+fn roundtrip_bigint() {
+    check_roundtrip::<ItfBigInt>();
+}
+
+#[test]
+fn roundtrip_tuple() {
+    check_roundtrip::<ItfTuple<(ItfBigInt, ItfTuple<(bool, String)>)>>();
+}
+
+#[test]
+fn roundtrip_set() {
+    check_roundtrip::<ItfSet<ItfBTreeSet<ItfBigInt>>>();
+    check_roundtrip::<ItfBTreeSet<ItfTuple<(i64, String)>>>();
+}
+
+#[test]
+fn roundtrip_map() {
+    check_roundtrip::<ItfMap<ItfTuple<(i64, String)>, ItfSet<ItfBigInt>>>();
+    check_roundtrip::<ItfBTreeMap<ItfBigInt, ItfMap<String, ItfTuple<(bool, ItfBigInt)>>>>();
+}
+
+#[test]
+fn roundtrip_plain_bigint_elements() {
+    check_roundtrip::<ItfSet<BigInt>>();
+    check_roundtrip::<ItfBTreeMap<BigInt, ItfSet<BigInt>>>();
+    check_roundtrip::<ItfTuple<(BigInt, ItfBTreeSet<BigInt>)>>();
+
+    check_same_encoding::<ItfBTreeSet<BigInt>, ItfBTreeSet<ItfBigInt>>();
+    check_same_encoding::<ItfBTreeMap<BigInt, BigInt>, ItfBTreeMap<ItfBigInt, ItfBigInt>>();
+    check_same_encoding::<ItfTuple<(BigInt, bool)>, ItfTuple<(ItfBigInt, bool)>>();
+}
+
+#[test]
+fn roundtrip_nested() {
+    check_roundtrip::<
+        ItfMap<ItfBTreeSet<String>, ItfTuple<(ItfSet<ItfBigInt>, ItfBTreeMap<i64, bool>)>>,
+    >();
+}
+
+#[test]
+fn roundtrip_value() {
+    check_roundtrip::<ItfValue>();
+}
+
+#[test]
+fn value_display_ignores_order() {
+    // Sets and maps are displayed in the same order whatever their order in the trace
+    fn reversed(value: ItfValue) -> ItfValue {
+        let rev = |elems: Vec<ItfValue>| elems.into_iter().rev().map(reversed).collect();
+
+        match value {
+            ItfValue::Set(elems) => ItfValue::Set(rev(elems)),
+            ItfValue::Map(entries) => ItfValue::Map(
+                entries
+                    .into_iter()
+                    .rev()
+                    .map(|(k, v)| (reversed(k), reversed(v)))
+                    .collect(),
+            ),
+            ItfValue::Tuple(elems) => ItfValue::Tuple(elems.into_iter().map(reversed).collect()),
+            ItfValue::List(elems) => ItfValue::List(elems.into_iter().map(reversed).collect()),
+            ItfValue::Record(fields) => {
+                ItfValue::Record(fields.into_iter().map(|(k, v)| (k, reversed(v))).collect())
+            }
+            value => value,
+        }
+    }
+
+    for seed in 1..=CASES as u64 {
+        let mut g = Gen(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let value = ItfValue::arbitrary(&mut g);
+
+        assert_eq!(
+            reversed(value.clone()).to_string(),
+            value.to_string(),
+            "seed {seed}"
+        );
+    }
+}